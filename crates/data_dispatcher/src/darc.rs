@@ -0,0 +1,626 @@
+use crate::{DispatcherError, DispatcherResult};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// DARC archive container:
+///
+/// ```{text}
+/// 0                   1
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |d|a|r|c|BOM|HDL|  VER  | FSIZE |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// | TBOFF | TBLEN | DTOFF |       |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+       +
+/// |     NODES (12 bytes each)     |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |  NAMES (UTF-16LE, null-term)  |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |   FILE DATA (aligned)         |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 0-3: magic, `darc`;
+/// 4-5: byte order mark, u16 little-endian (0xFEFF);
+/// 6-7: header length, u16 little-endian (0x1C);
+/// 8-11: version, u32 little-endian (0x01000000);
+/// 12-15: file size, u32 little-endian;
+/// 16-19: file table offset, u32 little-endian;
+/// 20-23: file table length (nodes + names), u32 little-endian;
+/// 24-27: file data offset, u32 little-endian;
+/// ```
+///
+/// each node of the file table:
+///
+/// ```{text}
+/// 0-3: name offset in the name table (bit 0-23), directory flag (bit 24), u32 little-endian;
+/// 4-7: data offset (file) or parent node index (directory), u32 little-endian;
+/// 8-11: data length (file) or index of the first node after the directory, u32 little-endian;
+/// ```
+///
+/// ## Note
+/// node 0 is the unnamed root directory, whose end index is the node count.
+/// archives built by the official tool put everything under a `.` directory,
+/// which is hidden from the entry paths and added back when repacking a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DarcArchive {
+    nodes: Vec<DarcNode>,
+    alignment: u32,
+}
+
+/// file entry of a [DarcArchive], `offset` is where the data is laid out in the archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DarcEntry {
+    pub path: String,
+    pub offset: u32,
+    pub length: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DarcNode {
+    name: String,
+    kind: DarcNodeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DarcNodeKind {
+    Directory { parent: u32, next: u32 },
+    File { data: Vec<u8> },
+}
+
+impl DarcArchive {
+    pub const MAGIC_HEADER: &[u8] = b"darc";
+
+    const BYTE_ORDER_MARK: u16 = 0xFEFF;
+    const HEADER_LENGTH: u16 = 0x1C;
+    const VERSION: u32 = 0x0100_0000;
+    const NODE_LENGTH: u32 = 12;
+    const DIRECTORY_FLAG: u32 = 0x0100_0000;
+    const DEFAULT_ALIGNMENT: u32 = 0x20;
+    const MAX_ALIGNMENT: u32 = 0x80;
+
+    pub fn is_darc(buffer: &[u8]) -> bool {
+        buffer.starts_with(Self::MAGIC_HEADER)
+    }
+
+    pub fn parse(buffer: &[u8]) -> DispatcherResult<Self> {
+        if !Self::is_darc(buffer) {
+            return Err(DispatcherError::InvalidArchive(
+                "not a darc archive: magic header `darc` not found".to_string(),
+            ));
+        }
+        let byte_order_mark = read_u16(buffer, 4)?;
+        if byte_order_mark != Self::BYTE_ORDER_MARK {
+            return Err(DispatcherError::InvalidArchive(format!(
+                "unsupported darc byte order mark {byte_order_mark:#06x}"
+            )));
+        }
+        let table_offset = read_u32(buffer, 16)? as usize;
+        let table_length = read_u32(buffer, 20)? as usize;
+        if table_offset
+            .checked_add(table_length)
+            .is_none_or(|table_end| table_end > buffer.len())
+        {
+            return Err(DispatcherError::InvalidArchive(format!(
+                "darc file table at offset {table_offset:#x} is out of bounds"
+            )));
+        }
+
+        // root node: its end index is the node count, which the file table must hold.
+        let node_count = read_u32(buffer, table_offset + 8)? as usize;
+        let names_offset = node_count
+            .checked_mul(Self::NODE_LENGTH as usize)
+            .filter(|&nodes_length| nodes_length <= table_length)
+            .map(|nodes_length| table_offset + nodes_length)
+            .ok_or_else(|| {
+                DispatcherError::InvalidArchive(format!(
+                    "darc file table of {table_length:#x} bytes can not hold {node_count} nodes"
+                ))
+            })?;
+
+        let mut nodes = Vec::with_capacity(node_count);
+        let mut alignment = Self::MAX_ALIGNMENT;
+        for index in 0..node_count {
+            let node_offset = table_offset + index * Self::NODE_LENGTH as usize;
+            let name_field = read_u32(buffer, node_offset)?;
+            let offset_field = read_u32(buffer, node_offset + 4)?;
+            let length_field = read_u32(buffer, node_offset + 8)?;
+
+            let name = read_name(
+                buffer,
+                names_offset + (name_field & !Self::DIRECTORY_FLAG) as usize,
+            )?;
+            let kind = if name_field & Self::DIRECTORY_FLAG != 0 {
+                DarcNodeKind::Directory {
+                    parent: offset_field,
+                    next: length_field,
+                }
+            } else {
+                let start = offset_field as usize;
+                let data = start
+                    .checked_add(length_field as usize)
+                    .and_then(|end| buffer.get(start..end))
+                    .ok_or_else(|| {
+                        DispatcherError::InvalidArchive(format!(
+                            "darc entry `{name}` is out of bounds"
+                        ))
+                    })?;
+                while offset_field % alignment != 0 {
+                    alignment /= 2;
+                }
+                DarcNodeKind::File {
+                    data: data.to_vec(),
+                }
+            };
+            nodes.push(DarcNode { name, kind });
+        }
+
+        Ok(Self {
+            nodes,
+            alignment: alignment.max(4),
+        })
+    }
+
+    /// build an archive from every file under `dir`, entries are sorted by name.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> DispatcherResult<Self> {
+        let mut nodes = vec![
+            DarcNode {
+                name: String::new(),
+                kind: DarcNodeKind::Directory { parent: 0, next: 0 },
+            },
+            DarcNode {
+                name: ".".to_string(),
+                kind: DarcNodeKind::Directory { parent: 0, next: 0 },
+            },
+        ];
+        push_dir(&mut nodes, dir.as_ref(), 1)?;
+
+        let node_count = to_u32(nodes.len() as u64, "node count")?;
+        for node in nodes.iter_mut().take(2) {
+            node.kind = DarcNodeKind::Directory {
+                parent: 0,
+                next: node_count,
+            };
+        }
+
+        Ok(Self {
+            nodes,
+            alignment: Self::DEFAULT_ALIGNMENT,
+        })
+    }
+
+    /// file entries with their full paths and the offsets they are laid out at.
+    pub fn entries(&self) -> DispatcherResult<Vec<DarcEntry>> {
+        let offsets = self.layout()?.1;
+        let paths = self.paths();
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| match &node.kind {
+                DarcNodeKind::File { data } => Some((index, data)),
+                DarcNodeKind::Directory { .. } => None,
+            })
+            .map(|(index, data)| {
+                Ok(DarcEntry {
+                    path: paths[index].clone(),
+                    offset: offsets[index],
+                    length: to_u32(data.len() as u64, "entry length")?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn entry_data(&self, path: &str) -> Option<&[u8]> {
+        let index = self.paths().iter().position(|p| p == path)?;
+        match &self.nodes[index].kind {
+            DarcNodeKind::File { data } => Some(data),
+            DarcNodeKind::Directory { .. } => None,
+        }
+    }
+
+    /// replace the data of an existing entry, offsets are recomputed by [DarcArchive::to_bytes].
+    pub fn set_entry_data(&mut self, path: &str, new_data: Vec<u8>) -> DispatcherResult<()> {
+        let index = self.paths().iter().position(|p| p == path).ok_or_else(|| {
+            DispatcherError::InvalidArchive(format!("darc entry `{path}` not found"))
+        })?;
+        match &mut self.nodes[index].kind {
+            DarcNodeKind::File { data } => *data = new_data,
+            DarcNodeKind::Directory { .. } => {
+                return Err(DispatcherError::InvalidArchive(format!(
+                    "darc entry `{path}` is a directory"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// write every file entry under `dir`, keeping the directory structure.
+    ///
+    /// nothing is written if an entry path would leave `dir`, like `../name` or an absolute
+    /// path.
+    pub fn extract<P: AsRef<Path>>(&self, dir: P) -> DispatcherResult<Vec<DarcEntry>> {
+        let entries = self.entries()?;
+        for entry in &entries {
+            let mut components = Path::new(&entry.path).components().peekable();
+            if components.peek().is_none()
+                || !components.all(|component| matches!(component, Component::Normal(_)))
+            {
+                return Err(DispatcherError::InvalidArchive(format!(
+                    "darc entry `{}` is not a relative path inside the archive",
+                    entry.path
+                )));
+            }
+        }
+        for entry in &entries {
+            let path = dir.as_ref().join(&entry.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let data = self.entry_data(&entry.path).unwrap_or_default();
+            fs::write(&path, data)?;
+        }
+        Ok(entries)
+    }
+
+    pub fn to_bytes(&self) -> DispatcherResult<Vec<u8>> {
+        let (names, offsets, data_offset, file_size) = self.layout()?;
+        let table_offset = Self::HEADER_LENGTH as u32;
+        let table_length = to_u32(
+            self.nodes.len() as u64 * Self::NODE_LENGTH as u64 + names.len() as u64,
+            "file table length",
+        )?;
+
+        let mut buffer = Vec::with_capacity(file_size as usize);
+        buffer.extend_from_slice(Self::MAGIC_HEADER);
+        buffer.extend_from_slice(&Self::BYTE_ORDER_MARK.to_le_bytes());
+        buffer.extend_from_slice(&Self::HEADER_LENGTH.to_le_bytes());
+        buffer.extend_from_slice(&Self::VERSION.to_le_bytes());
+        buffer.extend_from_slice(&file_size.to_le_bytes());
+        buffer.extend_from_slice(&table_offset.to_le_bytes());
+        buffer.extend_from_slice(&table_length.to_le_bytes());
+        buffer.extend_from_slice(&data_offset.to_le_bytes());
+
+        let mut name_offset = 0;
+        for (index, node) in self.nodes.iter().enumerate() {
+            let (flag, offset_field, length_field) = match &node.kind {
+                DarcNodeKind::Directory { parent, next } => (Self::DIRECTORY_FLAG, *parent, *next),
+                DarcNodeKind::File { data } => (
+                    0,
+                    offsets[index],
+                    to_u32(data.len() as u64, "entry length")?,
+                ),
+            };
+            buffer.extend_from_slice(&(name_offset | flag).to_le_bytes());
+            buffer.extend_from_slice(&offset_field.to_le_bytes());
+            buffer.extend_from_slice(&length_field.to_le_bytes());
+            // below the directory flag, checked by the layout.
+            name_offset += (node.name.encode_utf16().count() as u32 + 1) * 2;
+        }
+        buffer.extend_from_slice(&names);
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let DarcNodeKind::File { data } = &node.kind {
+                buffer.resize(offsets[index] as usize, 0);
+                buffer.extend_from_slice(data);
+            }
+        }
+        buffer.resize(file_size as usize, 0);
+
+        Ok(buffer)
+    }
+
+    /// name table, data offset of every node, start of the file data and total file size.
+    ///
+    /// offsets are computed on 64 bits, an archive whose offsets do not fit in the u32
+    /// fields is an [DispatcherError::InvalidArchive].
+    fn layout(&self) -> DispatcherResult<(Vec<u8>, Vec<u32>, u32, u32)> {
+        let names: Vec<u8> = self
+            .nodes
+            .iter()
+            .flat_map(|node| node.name.encode_utf16().chain([0]))
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        // the name offset shares its field with the directory flag.
+        if names.len() > Self::DIRECTORY_FLAG as usize {
+            return Err(DispatcherError::InvalidArchive(format!(
+                "darc name table of {:#x} bytes does not fit in the 24 bit name offsets",
+                names.len()
+            )));
+        }
+
+        let alignment = self.alignment as u64;
+        let table_end = Self::HEADER_LENGTH as u64
+            + self.nodes.len() as u64 * Self::NODE_LENGTH as u64
+            + names.len() as u64;
+        let data_offset = table_end.next_multiple_of(alignment);
+
+        let mut offsets = vec![0; self.nodes.len()];
+        let mut position = data_offset;
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let DarcNodeKind::File { data } = &node.kind {
+                position = position.next_multiple_of(alignment);
+                offsets[index] = to_u32(position, "data offset")?;
+                position += data.len() as u64;
+            }
+        }
+
+        Ok((
+            names,
+            offsets,
+            to_u32(data_offset, "data offset")?,
+            to_u32(position, "file size")?,
+        ))
+    }
+
+    /// full path of every node, the root and `.` directories are hidden.
+    fn paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = Vec::with_capacity(self.nodes.len());
+        let mut dirs: Vec<(u32, String)> = vec![];
+        for (index, node) in self.nodes.iter().enumerate() {
+            while dirs.last().is_some_and(|(next, _)| *next <= index as u32) {
+                dirs.pop();
+            }
+            let parent = dirs.last().map(|(_, path)| path.as_str()).unwrap_or("");
+            let path = match (parent, node.name.as_str()) {
+                (_, "") | (_, ".") => parent.to_string(),
+                ("", name) => name.to_string(),
+                (parent, name) => format!("{parent}/{name}"),
+            };
+            if let DarcNodeKind::Directory { next, .. } = node.kind {
+                dirs.push((next, path.clone()));
+            }
+            paths.push(path);
+        }
+        paths
+    }
+}
+
+/// append the entries under `dir` depth-first, `parent` is the node index of `dir`.
+fn push_dir(nodes: &mut Vec<DarcNode>, dir: &Path, parent: u32) -> DispatcherResult<()> {
+    let mut children: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    children.sort_by_key(|path| path.file_name().unwrap_or_default().to_ascii_lowercase());
+
+    for child in children {
+        let name = child
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        if child.is_dir() {
+            let index = nodes.len();
+            nodes.push(DarcNode {
+                name,
+                kind: DarcNodeKind::Directory { parent, next: 0 },
+            });
+            push_dir(nodes, &child, to_u32(index as u64, "node index")?)?;
+            let next = to_u32(nodes.len() as u64, "node count")?;
+            nodes[index].kind = DarcNodeKind::Directory { parent, next };
+        } else {
+            nodes.push(DarcNode {
+                name,
+                kind: DarcNodeKind::File {
+                    data: fs::read(&child)?,
+                },
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// `value` written in a u32 field of the archive.
+fn to_u32(value: u64, field: &str) -> DispatcherResult<u32> {
+    u32::try_from(value).map_err(|_| {
+        DispatcherError::InvalidArchive(format!("darc {field} {value:#x} does not fit in 32 bits"))
+    })
+}
+
+fn read_u16(buffer: &[u8], offset: usize) -> DispatcherResult<u16> {
+    buffer
+        .get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| {
+            DispatcherError::InvalidArchive(format!(
+                "darc archive is truncated at offset {offset:#x}"
+            ))
+        })
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> DispatcherResult<u32> {
+    buffer
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| {
+            DispatcherError::InvalidArchive(format!(
+                "darc archive is truncated at offset {offset:#x}"
+            ))
+        })
+}
+
+/// read a null-terminated UTF-16LE name.
+fn read_name(buffer: &[u8], mut offset: usize) -> DispatcherResult<String> {
+    let mut units = vec![];
+    loop {
+        match read_u16(buffer, offset)? {
+            0 => break,
+            unit => units.push(unit),
+        }
+        offset += 2;
+    }
+    String::from_utf16(&units)
+        .map_err(|e| DispatcherError::InvalidArchive(format!("invalid darc entry name: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `.` directory holding `data/a.bin` and `b.bin`, as built by the official tool.
+    fn archive() -> DarcArchive {
+        let directory = |name: &str, parent, next| DarcNode {
+            name: name.to_string(),
+            kind: DarcNodeKind::Directory { parent, next },
+        };
+        let file = |name: &str, data: &[u8]| DarcNode {
+            name: name.to_string(),
+            kind: DarcNodeKind::File {
+                data: data.to_vec(),
+            },
+        };
+        DarcArchive {
+            nodes: vec![
+                directory("", 0, 5),
+                directory(".", 0, 5),
+                directory("data", 1, 4),
+                file("a.bin", b"[STRTBL]"),
+                file("b.bin", &[0xFF; 3]),
+            ],
+            alignment: DarcArchive::DEFAULT_ALIGNMENT,
+        }
+    }
+
+    fn set_u32(buffer: &mut [u8], offset: usize, value: u32) {
+        buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn invalid_archive(buffer: &[u8]) -> String {
+        match DarcArchive::parse(buffer) {
+            Err(DispatcherError::InvalidArchive(reason)) => reason,
+            other => panic!("expected an invalid archive, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_and_repack_round_trip() {
+        let bytes = archive().to_bytes().unwrap();
+        assert!(DarcArchive::is_darc(&bytes));
+        let parsed = DarcArchive::parse(&bytes).unwrap();
+        assert_eq!(parsed, archive());
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+
+        let entries = parsed.entries().unwrap();
+        let paths: Vec<_> = entries.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["data/a.bin", "b.bin"]);
+        assert!(entries.iter().all(|entry| entry.offset % 0x20 == 0));
+        assert_eq!(parsed.entry_data("data/a.bin"), Some(&b"[STRTBL]"[..]));
+        assert_eq!(parsed.entry_data("data"), None);
+    }
+
+    #[test]
+    fn replaced_entry_data_is_laid_out_again() {
+        let mut archive = archive();
+        archive
+            .set_entry_data("data/a.bin", vec![0x41; 0x30])
+            .unwrap();
+        assert!(archive.set_entry_data("data", vec![]).is_err());
+        assert!(archive.set_entry_data("c.bin", vec![]).is_err());
+
+        let parsed = DarcArchive::parse(&archive.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.entry_data("data/a.bin"), Some(&[0x41; 0x30][..]));
+        assert_eq!(parsed.entry_data("b.bin"), Some(&[0xFF; 3][..]));
+        let entries = parsed.entries().unwrap();
+        assert_eq!(entries[1].offset, entries[0].offset + 0x40);
+    }
+
+    #[test]
+    fn extract_and_repack_a_directory() {
+        let dir = std::env::temp_dir().join(format!("darc-repack-{}", std::process::id()));
+        let entries = archive().extract(&dir).unwrap();
+        assert_eq!(entries, archive().entries().unwrap());
+        assert_eq!(fs::read(dir.join("data/a.bin")).unwrap(), b"[STRTBL]");
+
+        let repacked = DarcArchive::from_dir(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let repacked = repacked.unwrap();
+        let paths: Vec<_> = repacked
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        assert_eq!(paths, ["b.bin", "data/a.bin"]);
+        assert_eq!(repacked.entry_data("b.bin"), Some(&[0xFF; 3][..]));
+    }
+
+    #[test]
+    fn other_files_are_not_archives() {
+        assert!(!DarcArchive::is_darc(b"[STRTBL]"));
+        assert!(invalid_archive(b"[STRTBL]").contains("magic header"));
+        let bytes = archive().to_bytes().unwrap();
+        assert!(DarcArchive::parse(&bytes[..0x10]).is_err());
+    }
+
+    #[test]
+    fn node_count_beyond_the_file_table_is_rejected() {
+        let mut buffer = archive().to_bytes().unwrap();
+        let table_offset = DarcArchive::HEADER_LENGTH as usize;
+        set_u32(&mut buffer, table_offset + 8, u32::MAX);
+        assert!(invalid_archive(&buffer).contains("can not hold"));
+    }
+
+    #[test]
+    fn file_table_beyond_the_archive_is_rejected() {
+        let mut buffer = archive().to_bytes().unwrap();
+        set_u32(&mut buffer, 16, u32::MAX);
+        assert!(invalid_archive(&buffer).contains("out of bounds"));
+
+        let mut buffer = archive().to_bytes().unwrap();
+        set_u32(&mut buffer, 20, u32::MAX);
+        assert!(invalid_archive(&buffer).contains("out of bounds"));
+    }
+
+    #[test]
+    fn file_data_beyond_the_archive_is_rejected() {
+        let mut buffer = archive().to_bytes().unwrap();
+        let node_offset = DarcArchive::HEADER_LENGTH as usize + 3 * 12;
+        set_u32(&mut buffer, node_offset + 4, u32::MAX - 1);
+        set_u32(&mut buffer, node_offset + 8, u32::MAX);
+        assert!(invalid_archive(&buffer).contains("`a.bin` is out of bounds"));
+    }
+
+    #[test]
+    fn offsets_beyond_32_bits_are_rejected() {
+        // the second entry is aligned to 4 GiB.
+        let mut archive = archive();
+        archive.alignment = 0x8000_0000;
+        for result in [archive.to_bytes().err(), archive.entries().err()] {
+            match result {
+                Some(DispatcherError::InvalidArchive(reason)) => {
+                    assert_eq!(
+                        reason,
+                        "darc data offset 0x100000000 does not fit in 32 bits"
+                    )
+                }
+                other => panic!("expected an invalid archive, got {other:?}"),
+            }
+        }
+
+        let mut archive = self::archive();
+        archive.nodes[4].name = "b".repeat(0x80_0000);
+        match archive.to_bytes() {
+            Err(DispatcherError::InvalidArchive(reason)) => {
+                assert!(reason.contains("24 bit name offsets"), "{reason}")
+            }
+            other => panic!("expected an invalid archive, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn extract_rejects_entries_leaving_the_directory() {
+        let dir = std::env::temp_dir().join(format!("darc-extract-{}", std::process::id()));
+        for name in ["..", "/etc"] {
+            let mut escaping = archive();
+            escaping.nodes[2].name = name.to_string();
+            match escaping.extract(&dir) {
+                Err(DispatcherError::InvalidArchive(reason)) => {
+                    assert!(reason.contains("not a relative path"), "{reason}")
+                }
+                other => panic!("expected an invalid archive, got {other:?}"),
+            }
+            assert!(!dir.exists());
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! helpers called by the code generated by [crate::BinaryPatch], also shared by the
+//! schema driven tables.
+
+use crate::{
+    DeserializePatch, DispatcherError, DispatcherResult, SerializePatch, text::TextEncoding,
+};
+use std::io::{Read, Seek, Write};
+use utils::{BinaryError, align_up, stream_len};
+
+pub use utils::{ReadBinary, WriteBinary};
+
+/// map the errors raised by the header fields of a table.
+pub fn in_header(table: &str, offset: u64) -> impl Fn(BinaryError) -> DispatcherError + Copy {
+    move |e| DispatcherError::in_header(table, offset, e)
+}
+
+pub fn negate(raw_data: &mut [u8]) {
+    raw_data.iter_mut().for_each(|byte| *byte = !*byte);
+}
+
+/// pad `raw_data` with nulls until `head_length` bytes followed by it are aligned.
+pub fn pad_to_alignment(raw_data: &mut Vec<u8>, head_length: u64, alignment: u64) {
+    let length = head_length + raw_data.len() as u64;
+    let padding = align_up(length, alignment) - length;
+    raw_data.resize(raw_data.len() + padding as usize, 0);
+}
+
+/// item count field of a table holding `count` items.
+pub fn item_count<T: TryFrom<usize>>(table: &str, count: usize) -> DispatcherResult<T> {
+    T::try_from(count).map_err(|_| DispatcherError::TooManyItems {
+        table: table.to_string(),
+        count,
+    })
+}
+
+/// read `count` items, errors are reported with the item index and offset.
+pub fn read_items<T: DeserializePatch, R: Read + Seek>(
+    reader: &mut R,
+    encoding: TextEncoding,
+    table: &str,
+    count: usize,
+) -> DispatcherResult<Vec<T>> {
+    read_items_with(reader, table, count, |reader| {
+        T::deserialize_patch(reader, encoding)
+    })
+}
+
+/// read `count` items with `read_item`, errors are reported with the item index and offset.
+pub fn read_items_with<T, R: Read + Seek>(
+    reader: &mut R,
+    table: &str,
+    count: usize,
+    mut read_item: impl FnMut(&mut R) -> DispatcherResult<T>,
+) -> DispatcherResult<Vec<T>> {
+    let end_pos = stream_len(reader)?;
+    let mut items = vec![];
+    for index in 0..count {
+        let item_pos = reader.stream_position()?;
+        if item_pos >= end_pos {
+            return Err(DispatcherError::CountMismatch {
+                table: table.to_string(),
+                expected: count,
+                actual: index,
+            });
+        }
+        let item = read_item(reader).map_err(|e| e.in_item(table, index, item_pos))?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// write every item, errors are reported with the item index and offset.
+pub fn write_items<T: SerializePatch, W: Write + Seek>(
+    writer: &mut W,
+    encoding: TextEncoding,
+    table: &str,
+    items: &[T],
+) -> DispatcherResult<()> {
+    write_items_with(writer, table, items, |writer, item| {
+        item.serialize_patch(writer, encoding)
+    })
+}
+
+/// write every item with `write_item`, errors are reported with the item index and offset.
+pub fn write_items_with<T, W: Write + Seek>(
+    writer: &mut W,
+    table: &str,
+    items: &[T],
+    mut write_item: impl FnMut(&mut W, &T) -> DispatcherResult<()>,
+) -> DispatcherResult<()> {
+    for (index, item) in items.iter().enumerate() {
+        let item_pos = writer.stream_position()?;
+        write_item(writer, item).map_err(|e| e.in_item(table, index, item_pos))?;
+    }
+    Ok(())
+}
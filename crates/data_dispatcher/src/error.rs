@@ -0,0 +1,152 @@
+use std::{io, path::PathBuf};
+use thiserror::Error;
+use utils::BinaryError;
+
+pub type DispatcherResult<T> = Result<T, DispatcherError>;
+
+/// error type of data_dispatcher.
+///
+/// binaries can turn it into [anyhow::Error] with `?` or [utils::IntoAnyResult].
+#[derive(Debug, Error)]
+pub enum DispatcherError {
+    #[error("header `{header}` not found")]
+    HeaderNotFound { header: String },
+
+    #[error("no known table header at offset {offset:#x}")]
+    UnknownHeader { offset: u64 },
+
+    #[error("`{table}` header at offset {offset:#x} is truncated")]
+    TruncatedHeader {
+        table: String,
+        offset: u64,
+        #[source]
+        source: BinaryError,
+    },
+
+    #[error("`{table}` item {index} at offset {offset:#x} is truncated")]
+    TruncatedItem {
+        table: String,
+        index: usize,
+        offset: u64,
+        #[source]
+        source: BinaryError,
+    },
+
+    #[error("`{table}` item {index} at offset {offset:#x} is invalid")]
+    InvalidItem {
+        table: String,
+        index: usize,
+        offset: u64,
+        #[source]
+        source: Box<DispatcherError>,
+    },
+
+    #[error("`{table}` declares {expected} items but the data ends after {actual}")]
+    CountMismatch {
+        table: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("`{table}` has too many items: {count}")]
+    TooManyItems { table: String, count: usize },
+
+    #[error("string `{text}` cannot be encoded as {encoding}")]
+    EncodingError {
+        text: String,
+        encoding: &'static str,
+    },
+
+    #[error("expected `{expected}`, found `{found}`")]
+    TableTypeMismatch {
+        expected: &'static str,
+        found: String,
+    },
+
+    #[error("invalid schema `{name}`: {reason}")]
+    InvalidSchema { name: String, reason: String },
+
+    #[error("invalid value for `{field}`: {reason}")]
+    InvalidFieldValue { field: String, reason: String },
+
+    #[error("`{table}` tables can not be exchanged as {format}")]
+    UnsupportedTable { table: String, format: &'static str },
+
+    #[error("invalid {format} file at line {line}: {reason}")]
+    InvalidTranslation {
+        format: &'static str,
+        line: usize,
+        reason: String,
+    },
+
+    /// displayed as `line:column: message`.
+    #[error(transparent)]
+    Ron(#[from] ron::error::SpannedError),
+
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+
+    /// displayed with the line and column.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// displayed with the line and column.
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml_ng::Error),
+
+    #[error("failed to load `{}`", path.display())]
+    LoadFailed {
+        path: PathBuf,
+        #[source]
+        source: Box<DispatcherError>,
+    },
+
+    #[error("invalid darc archive: {0}")]
+    InvalidArchive(String),
+
+    #[error("invalid xlsx workbook: {0}")]
+    InvalidWorkbook(String),
+
+    /// displayed with the line of the record.
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    /// field level error raised by the binary readers and writers.
+    #[error(transparent)]
+    Binary(#[from] BinaryError),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl DispatcherError {
+    /// attach the table, item index and item offset to an error raised by an item.
+    pub(crate) fn in_item(self, table: &str, index: usize, offset: u64) -> Self {
+        match self {
+            DispatcherError::Binary(source) if source.is_eof() => DispatcherError::TruncatedItem {
+                table: table.to_string(),
+                index,
+                offset,
+                source,
+            },
+            source => DispatcherError::InvalidItem {
+                table: table.to_string(),
+                index,
+                offset,
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// attach the table and its offset to an error raised while reading the header.
+    pub(crate) fn in_header(table: &str, offset: u64, source: BinaryError) -> Self {
+        match source.is_eof() {
+            true => DispatcherError::TruncatedHeader {
+                table: table.to_string(),
+                offset,
+                source,
+            },
+            false => DispatcherError::Binary(source),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{BinaryPatch, DataDispatcherHeader, text::DecodeIssue};
+use serde::{Deserialize, Serialize};
+
+/// item in file name table patch:
+/// ```{text}
+/// 0                   1
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |LEN|            DATA           |
+/// +-+-+                     +-+-+-+
+/// |                         | PAD |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 0-1: length, u16 little-endian;
+/// 2-: data, string (length bytes, padding to 2 bytes alignment);
+/// ```
+///
+/// ## Note
+/// the actual content of the string needs to be obtained by bitwise negation.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, BinaryPatch)]
+pub struct FileNameTableItem {
+    #[skip]
+    length: u16,
+    #[len_prefix(u16, store = length)]
+    #[encoding]
+    #[negate]
+    data: String,
+}
+
+impl FileNameTableItem {
+    /// length of the data as read from the binary file, recomputed when serializing.
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    pub fn set_data(&mut self, data: impl Into<String>) {
+        self.data = data.into();
+    }
+}
+
+/// structure of file name table patch:
+///
+/// ```{text}
+/// 0                   1
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |[|F|-|N|A|M|E|]|  CNT  |  UNK  |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |LEN|    DATA   |LEN|    DATA   |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 0-7: header, `[F-NAME]`;
+/// 8-11: item_count, u32 little-endian;
+/// 12-15: unknown (assume as magic number), u32 little-endian;
+/// 16-: item, [FileNameTableItem];
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, BinaryPatch)]
+#[magic(b"[F-NAME]")]
+pub struct FileNameTable {
+    #[le]
+    #[count_of(items)]
+    item_count: u32,
+    #[le]
+    assume_magic_number: u32,
+    #[items]
+    items: Vec<FileNameTableItem>,
+}
+
+impl FileNameTable {
+    /// item count as read from the binary file, recomputed when serializing.
+    pub fn item_count(&self) -> u32 {
+        self.item_count
+    }
+
+    pub fn assume_magic_number(&self) -> u32 {
+        self.assume_magic_number
+    }
+
+    pub fn items(&self) -> &[FileNameTableItem] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut Vec<FileNameTableItem> {
+        &mut self.items
+    }
+
+    /// items whose data could not be fully decoded, `base_offset` is the offset of the magic header.
+    pub fn decode_issues(&self, base_offset: u64) -> Vec<DecodeIssue> {
+        let mut offset = base_offset + 16;
+        self.items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let issue = DecodeIssue::check(Self::TABLE_NAME, index, None, offset, &item.data);
+                offset += 2 + item.length as u64;
+                issue
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeserializePatch, SerializePatch, TextEncoding};
+
+    /// file name table item of negated `raw_data`.
+    fn item(raw_data: &[u8]) -> Vec<u8> {
+        let mut bytes = (raw_data.len() as u16).to_le_bytes().to_vec();
+        bytes.extend(raw_data.iter().map(|byte| !byte));
+        bytes
+    }
+
+    fn table(names: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = b"[F-NAME]".to_vec();
+        bytes.extend((names.len() as u32).to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        for name in names {
+            bytes.extend(item(name));
+        }
+        bytes
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let bytes = table(&[b"bg01.png", b"\x8C\x8B\x8A\xF3.ogg"]);
+        let table = FileNameTable::from_bytes(&bytes, TextEncoding::Cp932).unwrap();
+        assert_eq!((table.item_count(), table.assume_magic_number()), (2, 1));
+        let data: Vec<_> = table.items().iter().map(FileNameTableItem::data).collect();
+        assert_eq!(data, ["bg01.png", "結希.ogg"]);
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), bytes);
+    }
+
+    #[test]
+    fn edited_items_are_negated_and_counted_again() {
+        let expected = table(&[b"bg02.png", b""]);
+        let mut table =
+            FileNameTable::from_bytes(&table(&[b"bg01.png"]), TextEncoding::Cp932).unwrap();
+        table.items_mut()[0].set_data("bg02.png");
+        table.items_mut().push(FileNameTableItem::default());
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), expected);
+    }
+
+    #[test]
+    fn undecodable_bytes_are_reported_and_kept() {
+        let bytes = table(&[b"ok", b"a\xA0"]);
+        let table = FileNameTable::from_bytes(&bytes, TextEncoding::Cp932).unwrap();
+        let issues = table.decode_issues(0);
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].index, issues[0].offset), (1, 20));
+        assert_eq!(issues[0].raw_bytes, [0xA0]);
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), bytes);
+    }
+}
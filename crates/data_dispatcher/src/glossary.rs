@@ -0,0 +1,164 @@
+use crate::{DispatcherError, DispatcherResult, DumpFormat, SheetFormat, sheet::read_records};
+use std::{fs, path::Path};
+
+const FORMAT: &str = "glossary";
+
+const COLUMNS: [&str; 3] = ["term", "translation", "notes"];
+
+/// term of a [Glossary] with the translation agreed on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GlossaryTerm {
+    /// term as written in the original texts.
+    pub term: String,
+    pub translation: String,
+    pub notes: String,
+}
+
+/// glossary shared by the translators, as a csv, tsv or xlsx spreadsheet.
+///
+/// | term | translation | notes              |
+/// |------|-------------|--------------------|
+/// | 結希 | Yuki        | the little sister  |
+///
+/// the columns are found by name like in a [crate::Sheet], only `term` and `translation`
+/// are needed. the terms found in a text are added to the notes of its entry in the
+/// translation dumps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Glossary {
+    pub terms: Vec<GlossaryTerm>,
+}
+
+impl Glossary {
+    /// load a glossary, a tsv or xlsx spreadsheet by its extension and csv otherwise.
+    pub fn load<P: AsRef<Path>>(path: P) -> DispatcherResult<Self> {
+        let path = path.as_ref();
+        let format = match DumpFormat::from_path(path) {
+            DumpFormat::Tsv => SheetFormat::Tsv,
+            DumpFormat::Xlsx => SheetFormat::Xlsx,
+            _ => SheetFormat::Csv,
+        };
+        fs::read(path)
+            .map_err(DispatcherError::from)
+            .and_then(|bytes| Self::from_bytes(&bytes, format))
+            .map_err(|e| DispatcherError::LoadFailed {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })
+    }
+
+    /// the terms of every glossary, in order.
+    pub fn load_all<P: AsRef<Path>>(paths: &[P]) -> DispatcherResult<Self> {
+        let mut glossary = Self::default();
+        for path in paths {
+            glossary.terms.extend(Self::load(path)?.terms);
+        }
+        Ok(glossary)
+    }
+
+    pub fn from_bytes(bytes: &[u8], format: SheetFormat) -> DispatcherResult<Self> {
+        let mut records = read_records(bytes, format)?
+            .into_iter()
+            .filter(|(_, cells)| cells.iter().any(|cell| !cell.trim().is_empty()));
+        let Some((header_line, header)) = records.next() else {
+            return Ok(Self::default());
+        };
+
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|cell| cell.trim().eq_ignore_ascii_case(name))
+        };
+        let [term, translation, notes] = COLUMNS.map(column);
+        let (Some(term), Some(translation)) = (term, translation) else {
+            return Err(DispatcherError::InvalidTranslation {
+                format: FORMAT,
+                line: header_line,
+                reason: "the first row names no `term` or no `translation` column".to_string(),
+            });
+        };
+
+        let terms = records
+            .map(|(_, cells)| {
+                let cell = |column: Option<usize>| {
+                    column
+                        .and_then(|column| cells.get(column))
+                        .map(|cell| cell.trim().to_string())
+                        .unwrap_or_default()
+                };
+                GlossaryTerm {
+                    term: cell(Some(term)),
+                    translation: cell(Some(translation)),
+                    notes: cell(notes),
+                }
+            })
+            .filter(|term| !term.term.is_empty())
+            .collect();
+        Ok(Self { terms })
+    }
+
+    /// notes for the translators: the terms found in `text` with their translation.
+    pub fn notes(&self, text: &str) -> Vec<String> {
+        self.terms
+            .iter()
+            .filter(|term| text.contains(&term.term))
+            .map(|term| match term.notes.as_str() {
+                "" => format!("glossary: {} = {}", term.term, term.translation),
+                notes => format!("glossary: {} = {} ({notes})", term.term, term.translation),
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terms_are_read_by_column_name() {
+        let csv = "Notes,Term,Translation\r\n\
+                   the little sister,結希,Yuki\r\n\
+                   ,,\r\n\
+                   ,お兄さん,big brother\r\n";
+        let glossary = Glossary::from_bytes(csv.as_bytes(), SheetFormat::Csv).unwrap();
+        assert_eq!(
+            glossary.terms,
+            [
+                GlossaryTerm {
+                    term: "結希".to_string(),
+                    translation: "Yuki".to_string(),
+                    notes: "the little sister".to_string(),
+                },
+                GlossaryTerm {
+                    term: "お兄さん".to_string(),
+                    translation: "big brother".to_string(),
+                    notes: String::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_columns_are_an_error() {
+        let error = Glossary::from_bytes(b"term\tnotes\n", SheetFormat::Tsv).unwrap_err();
+        assert!(error.to_string().contains("no `term` or no `translation`"));
+    }
+
+    #[test]
+    fn notes_list_the_terms_found_in_the_text() {
+        let tsv =
+            "term\ttranslation\tnotes\n結希\tYuki\tthe little sister\nお兄さん\tbig brother\t\n";
+        let glossary = Glossary::from_bytes(tsv.as_bytes(), SheetFormat::Tsv).unwrap();
+        assert_eq!(
+            glossary.notes("「お兄さん、結希です」"),
+            [
+                "glossary: 結希 = Yuki (the little sister)",
+                "glossary: お兄さん = big brother",
+            ]
+        );
+        assert!(glossary.notes("「おはよう」").is_empty());
+    }
+}
@@ -0,0 +1,728 @@
+extern crate self as data_dispatcher;
+
+mod darc;
+#[doc(hidden)]
+pub mod derive_support;
+mod error;
+mod file_name_table;
+mod glossary;
+mod manifest;
+mod name_table;
+mod patch;
+mod po;
+mod project;
+mod raw_table;
+mod scan;
+mod schema;
+mod sheet;
+mod string_table;
+mod text;
+mod translation;
+mod validate;
+mod xliff;
+
+pub use darc::{DarcArchive, DarcEntry};
+pub use data_dispatcher_derive::BinaryPatch;
+pub use error::{DispatcherError, DispatcherResult};
+pub use file_name_table::{FileNameTable, FileNameTableItem};
+pub use glossary::{Glossary, GlossaryTerm};
+pub use manifest::{ArchiveManifest, DumpFormat, MANIFEST_FILE_NAME, Manifest};
+pub use name_table::{NameTable, NameTableItem};
+pub use po::{PoEntry, PoFile};
+pub use project::{ProjectEntry, TranslationProject};
+pub use raw_table::{RawTable, RawTableConfig, RawTableItem};
+pub use scan::{CountGuess, LayoutGuess, ScanHit, scan};
+pub use schema::{FieldKind, FieldSchema, FieldValue, SchemaItem, SchemaTable, TableSchema};
+pub use sheet::{Sheet, SheetFormat, SheetRow};
+pub use string_table::{StringTable, StringTableItem, StringTerminator};
+pub use text::{DecodeIssue, TextEncoding, escape_raw_byte, unescape_raw_byte};
+pub use translation::{ImportSummary, TextEntry, TranslationStatus};
+pub use validate::{Check, Finding, Severity, validate};
+pub use xliff::{XliffFile, XliffState, XliffUnit, XliffVersion};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+#[allow(unused_imports)]
+use utils::IntoAnyResult;
+
+// 0                   1
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     HEADER    |  CNT  |  UNK  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                               |
+// +            PAYLOAD            +
+// |                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+pub trait DataDispatcherHeader: DeserializePatch {
+    const MAGIC_HEADER: &[u8];
+    const TABLE_NAME: &str;
+}
+
+/// deserialize trait for reading data from binary file.
+///
+/// the patch is read from the current position of `reader`, which is left right after it.
+/// item texts are decoded with `encoding`, the game itself uses [TextEncoding::Cp932].
+pub trait DeserializePatch: Sized {
+    fn deserialize_patch<R: Read + Seek>(
+        reader: &mut R,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<Self>;
+
+    /// read the patch at the start of `bytes`, without copying them.
+    fn from_bytes(bytes: &[u8], encoding: TextEncoding) -> DispatcherResult<Self> {
+        Self::deserialize_patch(&mut Cursor::new(bytes), encoding)
+    }
+}
+
+/// serialize trait for writing data back to binary file, symmetric to [DeserializePatch].
+///
+/// ## Note
+/// length and count fields are recomputed from the data, so an unmodified dump
+/// reproduces the original bytes exactly.
+pub trait SerializePatch {
+    fn serialize_patch<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<()>;
+
+    fn to_bytes(&self, encoding: TextEncoding) -> DispatcherResult<Vec<u8>> {
+        let mut writer = Cursor::new(vec![]);
+        self.serialize_patch(&mut writer, encoding)?;
+        Ok(writer.into_inner())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum DataDispatcher {
+    StringTable(StringTable),
+    NameTable(NameTable),
+    FileNameTable(FileNameTable),
+    /// table read with a [TableSchema].
+    Schema(SchemaTable),
+    /// table the crate doesn't know, read with a [RawTableConfig].
+    RawTable(RawTable),
+}
+
+/// loader trait for reading the ron, json or yaml dumped by data_dispatcher back into typed
+/// values.
+///
+/// errors from the parsers are reported with line and column numbers.
+pub trait LoadDump: Sized {
+    /// unwrap the expected table from the parsed dump.
+    fn from_dispatcher(data: DataDispatcher) -> DispatcherResult<Self>;
+
+    fn from_ron_str(ron_string: &str) -> DispatcherResult<Self> {
+        // [ron::error::SpannedError] is displayed as `line:column: message`.
+        let data = ron::from_str::<DataDispatcher>(ron_string)?;
+        Self::from_dispatcher(data)
+    }
+
+    fn from_json_str(json_string: &str) -> DispatcherResult<Self> {
+        let data = serde_json::from_str::<DataDispatcher>(json_string)?;
+        Self::from_dispatcher(data)
+    }
+
+    /// enums are read as single key maps, as written by [DataDispatcher::to_yaml_string].
+    fn from_yaml_str(yaml_string: &str) -> DispatcherResult<Self> {
+        let deserializer = serde_yaml_ng::Deserializer::from_str(yaml_string);
+        let data = serde_yaml_ng::with::singleton_map_recursive::deserialize(deserializer)?;
+        Self::from_dispatcher(data)
+    }
+
+    fn load_ron<P: AsRef<Path>>(path: P) -> DispatcherResult<Self> {
+        let path = path.as_ref();
+        let ron_string = fs::read_to_string(path)?;
+        Self::from_ron_str(&ron_string).map_err(|e| DispatcherError::LoadFailed {
+            path: path.to_path_buf(),
+            source: Box::new(e),
+        })
+    }
+
+    /// load a ron, json or yaml dump, told apart by the extension of `path`. ron if the
+    /// extension is unknown.
+    fn load_dump<P: AsRef<Path>>(path: P) -> DispatcherResult<Self> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(DispatcherError::from)
+            .and_then(|dump_string| match DumpFormat::from_path(path) {
+                DumpFormat::Json => Self::from_json_str(&dump_string),
+                DumpFormat::Yaml => Self::from_yaml_str(&dump_string),
+                _ => Self::from_ron_str(&dump_string),
+            })
+            .map_err(|e| DispatcherError::LoadFailed {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })
+    }
+}
+
+impl DataDispatcher {
+    /// name of the table type, the schema name for schema tables.
+    pub fn type_name(&self) -> &str {
+        match self {
+            DataDispatcher::StringTable(_) => StringTable::TABLE_NAME,
+            DataDispatcher::NameTable(_) => NameTable::TABLE_NAME,
+            DataDispatcher::FileNameTable(_) => FileNameTable::TABLE_NAME,
+            DataDispatcher::Schema(schema_table) => &schema_table.schema().name,
+            DataDispatcher::RawTable(_) => "RawTable",
+        }
+    }
+
+    /// type of the table, none for schema and raw tables.
+    pub fn patch_type(&self) -> Option<DataDispatcherType> {
+        match self.layout() {
+            Layout::Type(patch_type) => Some(patch_type),
+            Layout::Schema(_) | Layout::Raw(_) => None,
+        }
+    }
+
+    fn layout(&self) -> Layout<'_> {
+        match self {
+            DataDispatcher::StringTable(_) => Layout::Type(DataDispatcherType::StringTable),
+            DataDispatcher::NameTable(_) => Layout::Type(DataDispatcherType::NameTable),
+            DataDispatcher::FileNameTable(_) => Layout::Type(DataDispatcherType::FileNameTable),
+            DataDispatcher::Schema(schema_table) => Layout::Schema(schema_table.schema()),
+            DataDispatcher::RawTable(raw_table) => Layout::Raw(raw_table.config()),
+        }
+    }
+
+    pub fn magic_header(&self) -> &[u8] {
+        self.layout().magic_header()
+    }
+
+    /// read a table of the same type (or schema, or raw layout) as this one from the current position of
+    /// `reader`.
+    pub fn deserialize_same<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<DataDispatcher> {
+        self.layout().deserialize_patch(reader, encoding)
+    }
+
+    /// header fields after the magic header, as read from the binary file.
+    pub fn header_fields(&self) -> Vec<(String, String)> {
+        let field = |name: &str, value: &dyn ToString| (name.to_string(), value.to_string());
+        match self {
+            DataDispatcher::StringTable(string_table) => vec![
+                field("item_count", &string_table.item_count()),
+                field("assume_magic_number", &string_table.assume_magic_number()),
+            ],
+            DataDispatcher::NameTable(name_table) => vec![
+                field("assume_padding", &name_table.assume_padding()),
+                field("item_count", &name_table.item_count()),
+            ],
+            DataDispatcher::FileNameTable(fname_table) => vec![
+                field("item_count", &fname_table.item_count()),
+                field("assume_magic_number", &fname_table.assume_magic_number()),
+            ],
+            DataDispatcher::Schema(schema_table) => schema_table
+                .schema()
+                .header
+                .iter()
+                .filter_map(|schema_field| {
+                    let value = match schema_table.header().get(&schema_field.name)? {
+                        FieldValue::Int(value) => value.to_string(),
+                        FieldValue::Text(text) => format!("{text:?}"),
+                    };
+                    Some((schema_field.name.clone(), value))
+                })
+                .collect(),
+            DataDispatcher::RawTable(raw_table) => vec![
+                field("item_count", &raw_table.items().len()),
+                field("header", &format!("{:02X?}", raw_table.header())),
+            ],
+        }
+    }
+
+    /// the yaml dump, enums written as single key maps like in json rather than as yaml tags,
+    /// so that any yaml parser can read it.
+    pub fn to_yaml_string(&self) -> DispatcherResult<String> {
+        let mut yaml = vec![];
+        let mut serializer = serde_yaml_ng::Serializer::new(&mut yaml);
+        serde_yaml_ng::with::singleton_map_recursive::serialize(self, &mut serializer)?;
+        Ok(String::from_utf8_lossy(&yaml).into_owned())
+    }
+
+    /// number of items in the table.
+    pub fn len(&self) -> usize {
+        match self {
+            DataDispatcher::StringTable(string_table) => string_table.items().len(),
+            DataDispatcher::NameTable(name_table) => name_table.items().len(),
+            DataDispatcher::FileNameTable(fname_table) => fname_table.items().len(),
+            DataDispatcher::Schema(schema_table) => schema_table.items().len(),
+            DataDispatcher::RawTable(raw_table) => raw_table.items().len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// items whose data could not be fully decoded, `base_offset` is the offset of the magic header.
+    pub fn decode_issues(&self, base_offset: u64) -> Vec<DecodeIssue> {
+        match self {
+            DataDispatcher::StringTable(string_table) => string_table.decode_issues(base_offset),
+            DataDispatcher::NameTable(name_table) => name_table.decode_issues(base_offset),
+            DataDispatcher::FileNameTable(fname_table) => fname_table.decode_issues(base_offset),
+            DataDispatcher::Schema(schema_table) => schema_table.decode_issues(base_offset),
+            DataDispatcher::RawTable(raw_table) => raw_table.decode_issues(base_offset),
+        }
+    }
+}
+
+impl LoadDump for DataDispatcher {
+    fn from_dispatcher(data: DataDispatcher) -> DispatcherResult<Self> {
+        Ok(data)
+    }
+}
+
+impl LoadDump for StringTable {
+    fn from_dispatcher(data: DataDispatcher) -> DispatcherResult<Self> {
+        match data {
+            DataDispatcher::StringTable(string_table) => Ok(string_table),
+            other => Err(DispatcherError::TableTypeMismatch {
+                expected: StringTable::TABLE_NAME,
+                found: other.type_name().to_string(),
+            }),
+        }
+    }
+}
+
+impl LoadDump for NameTable {
+    fn from_dispatcher(data: DataDispatcher) -> DispatcherResult<Self> {
+        match data {
+            DataDispatcher::NameTable(name_table) => Ok(name_table),
+            other => Err(DispatcherError::TableTypeMismatch {
+                expected: NameTable::TABLE_NAME,
+                found: other.type_name().to_string(),
+            }),
+        }
+    }
+}
+
+impl LoadDump for FileNameTable {
+    fn from_dispatcher(data: DataDispatcher) -> DispatcherResult<Self> {
+        match data {
+            DataDispatcher::FileNameTable(fname_table) => Ok(fname_table),
+            other => Err(DispatcherError::TableTypeMismatch {
+                expected: FileNameTable::TABLE_NAME,
+                found: other.type_name().to_string(),
+            }),
+        }
+    }
+}
+
+impl LoadDump for SchemaTable {
+    fn from_dispatcher(data: DataDispatcher) -> DispatcherResult<Self> {
+        match data {
+            DataDispatcher::Schema(schema_table) => Ok(schema_table),
+            other => Err(DispatcherError::TableTypeMismatch {
+                expected: "SchemaTable",
+                found: other.type_name().to_string(),
+            }),
+        }
+    }
+}
+
+impl LoadDump for RawTable {
+    fn from_dispatcher(data: DataDispatcher) -> DispatcherResult<Self> {
+        match data {
+            DataDispatcher::RawTable(raw_table) => Ok(raw_table),
+            other => Err(DispatcherError::TableTypeMismatch {
+                expected: "RawTable",
+                found: other.type_name().to_string(),
+            }),
+        }
+    }
+}
+
+/// empty table of the type, to read tables of this type with [DataDispatcher::read_nth].
+impl From<DataDispatcherType> for DataDispatcher {
+    fn from(patch_type: DataDispatcherType) -> Self {
+        match patch_type {
+            DataDispatcherType::StringTable => DataDispatcher::StringTable(StringTable::default()),
+            DataDispatcherType::NameTable => DataDispatcher::NameTable(NameTable::default()),
+            DataDispatcherType::FileNameTable => {
+                DataDispatcher::FileNameTable(FileNameTable::default())
+            }
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DataDispatcherType {
+    StringTable,
+    NameTable,
+    FileNameTable,
+}
+
+impl DataDispatcherType {
+    /// every known table type, in the order they are probed by auto-detection.
+    pub const ALL: [DataDispatcherType; 3] = [
+        DataDispatcherType::StringTable,
+        DataDispatcherType::NameTable,
+        DataDispatcherType::FileNameTable,
+    ];
+
+    pub fn table_name(self) -> &'static str {
+        match self {
+            DataDispatcherType::StringTable => StringTable::TABLE_NAME,
+            DataDispatcherType::NameTable => NameTable::TABLE_NAME,
+            DataDispatcherType::FileNameTable => FileNameTable::TABLE_NAME,
+        }
+    }
+
+    /// the known table type named `table_name`, like [DataDispatcher::type_name].
+    pub fn from_table_name(table_name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|patch_type| patch_type.table_name() == table_name)
+    }
+
+    pub fn magic_header(self) -> &'static [u8] {
+        match self {
+            DataDispatcherType::StringTable => StringTable::MAGIC_HEADER,
+            DataDispatcherType::NameTable => NameTable::MAGIC_HEADER,
+            DataDispatcherType::FileNameTable => FileNameTable::MAGIC_HEADER,
+        }
+    }
+
+    /// type of the patch whose magic header is at the current position of `reader`,
+    /// the position is kept.
+    pub fn detect<R: Read + Seek>(reader: &mut R) -> DispatcherResult<Option<Self>> {
+        let start_pos = reader.stream_position()?;
+        let mut header = vec![];
+        for patch_type in Self::ALL {
+            let magic_header = patch_type.magic_header();
+            if header.len() < magic_header.len() {
+                reader.seek(SeekFrom::Start(start_pos))?;
+                header.clear();
+                reader
+                    .by_ref()
+                    .take(magic_header.len() as u64)
+                    .read_to_end(&mut header)?;
+            }
+            if header.starts_with(magic_header) {
+                reader.seek(SeekFrom::Start(start_pos))?;
+                return Ok(Some(patch_type));
+            }
+        }
+        reader.seek(SeekFrom::Start(start_pos))?;
+        Ok(None)
+    }
+
+    /// read a patch of this type from the current position of `reader`.
+    pub fn deserialize_patch<R: Read + Seek>(
+        self,
+        reader: &mut R,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<DataDispatcher> {
+        Ok(match self {
+            DataDispatcherType::StringTable => {
+                DataDispatcher::StringTable(StringTable::deserialize_patch(reader, encoding)?)
+            }
+            DataDispatcherType::NameTable => {
+                DataDispatcher::NameTable(NameTable::deserialize_patch(reader, encoding)?)
+            }
+            DataDispatcherType::FileNameTable => {
+                DataDispatcher::FileNameTable(FileNameTable::deserialize_patch(reader, encoding)?)
+            }
+        })
+    }
+}
+
+/// patch found in a container by [DataDispatcher::detect_all].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedPatch {
+    /// offset of the magic header in the container.
+    pub offset: usize,
+    /// size of the whole patch in bytes, magic header included.
+    pub length: usize,
+    pub data: DataDispatcher,
+}
+
+/// layout of a table, either a known type, a schema or a raw table layout.
+#[derive(Debug, Clone, Copy)]
+enum Layout<'a> {
+    Type(DataDispatcherType),
+    Schema(&'a TableSchema),
+    Raw(&'a RawTableConfig),
+}
+
+impl<'a> Layout<'a> {
+    fn magic_header(self) -> &'a [u8] {
+        match self {
+            Layout::Type(patch_type) => patch_type.magic_header(),
+            Layout::Schema(schema) => schema.magic_header(),
+            Layout::Raw(config) => config.magic_header(),
+        }
+    }
+
+    fn deserialize_patch<R: Read + Seek>(
+        self,
+        reader: &mut R,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<DataDispatcher> {
+        match self {
+            Layout::Type(patch_type) => patch_type.deserialize_patch(reader, encoding),
+            Layout::Schema(schema) => Ok(DataDispatcher::Schema(SchemaTable::deserialize_patch(
+                schema, reader, encoding,
+            )?)),
+            Layout::Raw(config) => Ok(DataDispatcher::RawTable(RawTable::deserialize_patch(
+                config, reader, encoding,
+            )?)),
+        }
+    }
+}
+
+impl DataDispatcher {
+    /// scan the whole buffer for the magic headers of `patch_types`, `schemas` and
+    /// `raw_tables`, and parse every patch found.
+    ///
+    /// patches are returned in the order they appear in the buffer, magic headers that
+    /// fall inside an already parsed patch are ignored.
+    pub fn detect_all(
+        buffer: &[u8],
+        patch_types: &[DataDispatcherType],
+        schemas: &[TableSchema],
+        raw_tables: &[RawTableConfig],
+        encoding: TextEncoding,
+    ) -> DispatcherResult<Vec<DetectedPatch>> {
+        let layouts = patch_types
+            .iter()
+            .map(|&patch_type| Layout::Type(patch_type))
+            .chain(schemas.iter().map(Layout::Schema))
+            .chain(raw_tables.iter().map(Layout::Raw));
+        let mut candidates = vec![];
+        for layout in layouts {
+            let header = layout.magic_header();
+            candidates.extend(
+                buffer
+                    .windows(header.len())
+                    .enumerate()
+                    .filter(|(_, window)| *window == header)
+                    .map(|(offset, _)| (offset, layout)),
+            );
+        }
+        candidates.sort_by_key(|(offset, _)| *offset);
+
+        let mut cursor = Cursor::new(buffer);
+        let mut patches: Vec<DetectedPatch> = vec![];
+        for (offset, patch_type) in candidates {
+            if patches
+                .last()
+                .is_some_and(|patch| offset < patch.offset + patch.length)
+            {
+                continue;
+            }
+
+            cursor.seek(SeekFrom::Start(offset as u64))?;
+            let data = patch_type.deserialize_patch(&mut cursor, encoding)?;
+            let length = cursor.position() as usize - offset;
+            patches.push(DetectedPatch {
+                offset,
+                length,
+                data,
+            });
+        }
+
+        Ok(patches)
+    }
+}
+
+/// the table type is picked by the magic header at the current position.
+impl DeserializePatch for DataDispatcher {
+    fn deserialize_patch<R: Read + Seek>(
+        reader: &mut R,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<Self> {
+        let offset = reader.stream_position()?;
+        match DataDispatcherType::detect(reader)? {
+            Some(patch_type) => patch_type.deserialize_patch(reader, encoding),
+            None => Err(DispatcherError::UnknownHeader { offset }),
+        }
+    }
+}
+
+impl SerializePatch for DataDispatcher {
+    fn serialize_patch<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<()> {
+        match self {
+            DataDispatcher::StringTable(string_table) => {
+                string_table.serialize_patch(writer, encoding)
+            }
+            DataDispatcher::NameTable(name_table) => name_table.serialize_patch(writer, encoding),
+            DataDispatcher::FileNameTable(fname_table) => {
+                fname_table.serialize_patch(writer, encoding)
+            }
+            DataDispatcher::Schema(schema_table) => schema_table.serialize_patch(writer, encoding),
+            DataDispatcher::RawTable(raw_table) => raw_table.serialize_patch(writer, encoding),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negated(data: &[u8]) -> Vec<u8> {
+        data.iter().map(|byte| !byte).collect()
+    }
+
+    /// `first` with id 1 and "結希" with id 2.
+    fn string_table(first: &[u8]) -> Vec<u8> {
+        let mut bytes = b"[STRTBL]".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        for (id, data) in [(1u32, first), (2, b"\x8C\x8B\x8A\xF3\0\0")] {
+            bytes.extend(id.to_le_bytes());
+            bytes.extend((data.len() as u16).to_le_bytes());
+            bytes.extend(negated(data));
+        }
+        bytes
+    }
+
+    /// "おはよう\n".
+    const GOOD_MORNING: &[u8] = b"\x82\xA8\x82\xCD\x82\xE6\x82\xA4\n\0";
+
+    fn name_table() -> Vec<u8> {
+        let mut bytes = b"[MESNAM]".to_vec();
+        bytes.extend([0, 0, 2, 0]);
+        bytes.extend(b"\x04\0\x8C\x8B\x8A\xF3\x04\0Yuki");
+        bytes
+    }
+
+    fn file_name_table() -> Vec<u8> {
+        let mut bytes = b"[F-NAME]".to_vec();
+        bytes.extend([1, 0, 0, 0, 1, 0, 0, 0, 9, 0]);
+        bytes.extend(negated(b"bg01.png\0"));
+        bytes
+    }
+
+    /// every kind of table read from its bytes.
+    fn tables() -> Vec<(DataDispatcher, Vec<u8>)> {
+        let encoding = TextEncoding::Cp932;
+        let string_table = string_table(GOOD_MORNING);
+        let name_table = name_table();
+        let file_name_table = file_name_table();
+        let schema = &TableSchema::builtin()[1];
+        let raw_config = RawTableConfig::new("[F-NAME]", FieldKind::U32);
+        vec![
+            (
+                DataDispatcher::StringTable(
+                    StringTable::from_bytes(&string_table, encoding).unwrap(),
+                ),
+                string_table,
+            ),
+            (
+                DataDispatcher::NameTable(NameTable::from_bytes(&name_table, encoding).unwrap()),
+                name_table.clone(),
+            ),
+            (
+                DataDispatcher::FileNameTable(
+                    FileNameTable::from_bytes(&file_name_table, encoding).unwrap(),
+                ),
+                file_name_table.clone(),
+            ),
+            (
+                DataDispatcher::Schema(
+                    SchemaTable::deserialize_patch(schema, &mut Cursor::new(&name_table), encoding)
+                        .unwrap(),
+                ),
+                name_table,
+            ),
+            (
+                DataDispatcher::RawTable(
+                    RawTable::deserialize_patch(
+                        &RawTableConfig {
+                            header_length: 8,
+                            ..raw_config
+                        },
+                        &mut Cursor::new(&file_name_table),
+                        encoding,
+                    )
+                    .unwrap(),
+                ),
+                file_name_table,
+            ),
+        ]
+    }
+
+    /// write `dump` as `file_name`, then patch `container` with it like `data_patcher`.
+    fn patch_with_dump(file_name: &str, dump: &str, container: &[u8]) -> Vec<u8> {
+        let dir = std::env::temp_dir().join(format!("load-dump-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(file_name);
+        fs::write(&path, dump).unwrap();
+        let loaded = DataDispatcher::load_patch(
+            &path,
+            DumpFormat::from_path(&path),
+            container,
+            TextEncoding::Cp932,
+            0,
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        let (data, summary) = loaded.unwrap();
+        assert_eq!(summary, ImportSummary::default());
+        data.patch(container, TextEncoding::Cp932).unwrap()
+    }
+
+    #[test]
+    fn json_and_yaml_dumps_patch_back_the_same_bytes() {
+        for (data, bytes) in tables() {
+            let json = serde_json::to_string_pretty(&data).unwrap();
+            assert_eq!(DataDispatcher::from_json_str(&json).unwrap(), data);
+            assert_eq!(patch_with_dump("dump.json", &json, &bytes), bytes);
+
+            let yaml = data.to_yaml_string().unwrap();
+            assert_eq!(DataDispatcher::from_yaml_str(&yaml).unwrap(), data);
+            assert_eq!(patch_with_dump("dump.yaml", &yaml, &bytes), bytes);
+            assert_eq!(patch_with_dump("dump.yml", &yaml, &bytes), bytes);
+        }
+    }
+
+    #[test]
+    fn edited_json_and_yaml_dumps_are_patched() {
+        let (data, bytes) = tables().swap_remove(0);
+        // "こんにちは\n", padded again to 4 bytes.
+        let expected = string_table(b"\x82\xB1\x82\xF1\x82\xC9\x82\xBF\x82\xCD\n\0\0\0");
+
+        let json = serde_json::to_string_pretty(&data).unwrap();
+        let json = json.replace("おはよう", "こんにちは");
+        assert_eq!(patch_with_dump("dump.json", &json, &bytes), expected);
+
+        let yaml = data.to_yaml_string().unwrap();
+        let yaml = yaml.replace("おはよう", "こんにちは");
+        assert_eq!(patch_with_dump("dump.yaml", &yaml, &bytes), expected);
+    }
+
+    #[test]
+    fn dumps_of_another_table_are_rejected() {
+        let (data, _) = tables().swap_remove(1);
+        let json = serde_json::to_string_pretty(&data).unwrap();
+        assert!(matches!(
+            StringTable::from_json_str(&json),
+            Err(DispatcherError::TableTypeMismatch { found, .. }) if found == "NameTable"
+        ));
+        let yaml = data.to_yaml_string().unwrap();
+        assert!(NameTable::from_yaml_str(&yaml).is_ok());
+        assert!(matches!(
+            FileNameTable::from_yaml_str(&yaml),
+            Err(DispatcherError::TableTypeMismatch { .. })
+        ));
+        assert!(DataDispatcher::from_json_str("{\"NameTable\": {}}").is_err());
+    }
+}
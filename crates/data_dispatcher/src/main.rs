@@ -1,439 +1,1267 @@
-use anyhow::Result as AnyResult;
-use clap::{Parser, Subcommand, ValueEnum};
-use encoding_rs::SHIFT_JIS;
+use anyhow::{Error as AnyError, Result as AnyResult, bail};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use data_dispatcher::{
+    ArchiveManifest, DarcArchive, DataDispatcher, DataDispatcherType, DetectedPatch,
+    DispatcherError, DumpFormat, FieldKind, Glossary, LoadDump, MANIFEST_FILE_NAME, Manifest,
+    PoFile, RawTableConfig, SerializePatch, Severity, Sheet, SheetFormat, TableSchema,
+    TextEncoding, TranslationProject, XliffFile, XliffVersion, scan, validate,
+};
 use ron::ser::{PrettyConfig, to_string_pretty};
 use serde::Serialize;
 use std::{
-    fs::File,
-    io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
 };
-#[allow(unused_imports)]
-use utils::IntoAnyResult;
+use thiserror::Error;
+
+/// any other error, clap exits with 2 on invalid arguments.
+const EXIT_FAILURE: u8 = 1;
+/// the input holds none of the requested tables.
+const EXIT_NOT_FOUND: u8 = 3;
+/// a table can not be rebuilt or loaded back from its dump.
+const EXIT_MISMATCH: u8 = 4;
+/// some files of a batch run failed, the others were processed.
+const EXIT_BATCH_FAILED: u8 = 5;
+/// validation found structural errors.
+const EXIT_INVALID: u8 = 6;
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  error
+  2  invalid arguments
+  3  no table found
+  4  round trip mismatch
+  5  some files of a batch run failed
+  6  validation found structural errors";
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, after_help = EXIT_CODES)]
 struct ConsoleArgs {
-    // input file path
+    /// Project manifest, `imojiru.toml` of the working directory if omitted. Gives the inputs,
+    /// outputs and encodings the arguments leave out
+    #[arg(short, long, global = true)]
+    manifest: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Dump the tables of a file or darc archive to ron, json, yaml, po, xliff, spreadsheets or
+    /// translation projects
+    Dump(DumpArgs),
+    /// Write a ron, json or yaml dump, or the translations of a po, xliff, spreadsheet or
+    /// translation project file, back into the original file or darc archive
+    Patch(PatchArgs),
+    /// Print the header fields, item counts and sizes of the tables
+    Info(InspectArgs),
+    /// Check the tables can be rebuilt from their ron, json and yaml dumps byte for byte
+    Verify(InspectArgs),
+    /// Check the structure of the tables: item counts, trailing bytes, padding, terminators
+    /// and header values
+    Validate(ValidateArgs),
+    /// Extract the entries of a darc archive into a directory
+    Extract(ExtractArgs),
+    /// Repack a directory into a darc archive
+    Repack(RepackArgs),
+    /// Report every `[......]` magic header with a guess at the layout of its table
+    Scan(ScanArgs),
+    /// Dump the tables of every darc archive of a game directory into a mirrored tree
+    DumpAll(DumpAllArgs),
+    /// Patch the dumps of a mirrored tree back into the darc archives of a game directory
+    #[command(visible_alias = "build")]
+    PatchAll(PatchAllArgs),
+}
+
+/// tables to look for in the input, the ones listed in the manifest if none is given.
+#[derive(Args, Debug)]
+struct TableArgs {
+    /// Patch type, detect every known table by its magic header if omitted
+    #[arg(short, long)]
+    patch_type: Option<DataDispatcherType>,
+
+    /// Schema file (ron or toml) or built-in schema name (StringTable, NameTable,
+    /// FileNameTable), may be repeated. Only the schema and raw tables are searched unless a
+    /// patch type is given too
+    #[arg(short, long)]
+    schema: Vec<String>,
+
+    /// Magic header of a table the crate doesn't know, read as u16 length-prefixed strings,
+    /// may be repeated. Searched like the schema tables
+    #[arg(long)]
+    raw: Vec<String>,
+
+    /// Type of the item count of the raw tables
+    #[arg(long, value_enum, default_value_t = FieldKind::U32, requires = "raw")]
+    raw_count: FieldKind,
+
+    /// Offset of the item count from the end of the raw table magic header
+    #[arg(long, default_value_t = 0, requires = "raw")]
+    raw_count_offset: usize,
+
+    /// Bytes between the raw table magic header and its first item, the item count only if
+    /// omitted
+    #[arg(long, requires = "raw")]
+    raw_header_length: Option<usize>,
+
+    /// Text encoding of the table items, the manifest source encoding or cp932 if omitted
+    #[arg(short, long, value_enum)]
+    encoding: Option<TextEncoding>,
+}
+
+#[derive(Args, Debug)]
+struct DumpArgs {
+    /// Input file or darc archive, `-` for stdin. Every archive of the manifest if omitted,
+    /// like `dump-all`
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Output file, `-` for stdout. Numbered by table type when several tables are found
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Dump format, by the output extension if omitted: json, yaml/yml, po template for
+    /// po/pot, xliff 1.2 for xlf, xliff 2.0 for xliff, string table spreadsheet for
+    /// csv/tsv/xlsx, translation project for project, and ron otherwise or on stdout
+    #[arg(short, long, value_enum)]
+    format: Option<DumpFormat>,
+
+    /// Glossary (csv, tsv or xlsx) whose terms are noted in the translation dumps, may be
+    /// repeated. The manifest glossaries if omitted
+    #[arg(short, long)]
+    glossary: Vec<PathBuf>,
+
+    #[command(flatten)]
+    tables: TableArgs,
+}
+
+#[derive(Args, Debug)]
+struct InspectArgs {
+    /// Input file or darc archive, `-` for stdin. Every archive of the manifest if omitted
+    #[arg(short, long)]
+    input: Option<String>,
+
+    #[command(flatten)]
+    tables: TableArgs,
+}
+
+#[derive(Args, Debug)]
+struct PatchArgs {
+    /// Ron, json or yaml dump, or po, xliff, spreadsheet or project file of translations, to write
+    /// back. Told apart by the extension, `-` for a ron dump on stdin
     #[arg(short, long)]
     input: String,
 
-    // output file path
+    /// Original file or darc archive, `-` for stdin
+    #[arg(short, long)]
+    container: String,
+
+    /// Output file, `-` for stdout
     #[arg(short, long)]
     output: String,
 
-    // patch type
+    /// Text encoding of the table items, the manifest target encoding or cp932 if omitted
+    #[arg(short, long, value_enum)]
+    encoding: Option<TextEncoding>,
+}
+
+#[derive(Args, Debug)]
+struct ValidateArgs {
+    /// Input file or darc archive, `-` for stdin. Every archive of the manifest and its
+    /// patched counterpart if omitted
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Print the findings as a json array
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args, Debug)]
+struct ExtractArgs {
+    /// Input darc archive, `-` for stdin
+    #[arg(short, long)]
+    input: String,
+
+    /// Output directory
+    #[arg(short, long)]
+    output: String,
+}
+
+#[derive(Args, Debug)]
+struct RepackArgs {
+    /// Input directory
+    #[arg(short, long)]
+    input: String,
+
+    /// Output darc archive, `-` for stdout
+    #[arg(short, long)]
+    output: String,
+}
+
+#[derive(Args, Debug)]
+struct ScanArgs {
+    /// Input file, directory searched for darc archives, or `-` for stdin. Every archive of
+    /// the manifest if omitted
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Text encoding used to tell negated texts from plain ones, the manifest source encoding
+    /// or cp932 if omitted
+    #[arg(short, long, value_enum)]
+    encoding: Option<TextEncoding>,
+}
+
+#[derive(Args, Debug)]
+struct DumpAllArgs {
+    /// Game directory, searched for darc archives. The archives of the manifest if omitted
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Output directory, mirroring the game directory. The manifest dump directory if omitted
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Dump format, may be repeated. The manifest formats, or ron, if omitted
+    #[arg(short, long, value_enum)]
+    format: Vec<DumpFormat>,
+
+    /// Glossary (csv, tsv or xlsx) whose terms are noted in the translation dumps, may be
+    /// repeated. The manifest glossaries if omitted
+    #[arg(short, long)]
+    glossary: Vec<PathBuf>,
+
+    #[command(flatten)]
+    tables: TableArgs,
+}
+
+#[derive(Args, Debug)]
+struct PatchAllArgs {
+    /// Directory of the dumps written by `dump-all`. The manifest dump directory if omitted
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Game directory the dumps were taken from. The archives of the manifest if omitted
+    #[arg(short, long)]
+    container: Option<String>,
+
+    /// Output directory, only the patched archives are written. The manifest patched
+    /// directory if omitted
     #[arg(short, long)]
-    patch_type: DataDispatcherType,
+    output: Option<String>,
+
+    /// Text encoding of the table items, the manifest target encoding or cp932 if omitted
+    #[arg(short, long, value_enum)]
+    encoding: Option<TextEncoding>,
+}
+
+/// errors with their own exit code.
+#[derive(Debug, Error)]
+enum Failure {
+    #[error("no table found in `{0}`")]
+    NotFound(String),
+
+    #[error("round trip mismatch: `{header}` can not be {reason}")]
+    Mismatch {
+        header: String,
+        reason: &'static str,
+    },
+
+    #[error("{failed} of {total} tables failed the round trip")]
+    VerifyFailed { failed: usize, total: usize },
+
+    #[error("{failed} files failed")]
+    BatchFailed { failed: usize },
+
+    #[error("{errors} structural errors found")]
+    Invalid { errors: usize },
+}
+
+fn exit_code(e: &AnyError) -> u8 {
+    match e.downcast_ref::<Failure>() {
+        Some(Failure::NotFound(_)) => EXIT_NOT_FOUND,
+        Some(Failure::Mismatch { .. } | Failure::VerifyFailed { .. }) => EXIT_MISMATCH,
+        Some(Failure::BatchFailed { .. }) => EXIT_BATCH_FAILED,
+        Some(Failure::Invalid { .. }) => EXIT_INVALID,
+        None => match e.downcast_ref::<DispatcherError>() {
+            Some(DispatcherError::HeaderNotFound { .. }) => EXIT_NOT_FOUND,
+            _ => EXIT_FAILURE,
+        },
+    }
 }
 
-// 0                   1
-// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
-// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-// |     HEADER    |  CNT  |  UNK  |
-// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-// |                               |
-// +            PAYLOAD            +
-// |                               |
-// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-pub trait DataDispatcherHeader: DeserializePatch {
-    const MAGIC_HEADER: &[u8];
+/// read the whole file, or stdin for `-`.
+fn read_input(path: &str) -> AnyResult<Vec<u8>> {
+    match path {
+        "-" => {
+            let mut buffer = vec![];
+            io::stdin().lock().read_to_end(&mut buffer)?;
+            Ok(buffer)
+        }
+        _ => Ok(fs::read(path)?),
+    }
 }
 
-/// deserialize trait for reading data from binary file.
-pub trait DeserializePatch {
-    fn deserialize_patch(&self, cursor: &mut Cursor<Vec<u8>>) -> AnyResult<Self>
-    where
-        Self: Sized;
+/// write the whole file, or stdout for `-`.
+fn write_output(path: &str, bytes: &[u8]) -> AnyResult<()> {
+    let mut writer: Box<dyn Write> = match path {
+        "-" => Box::new(io::stdout().lock()),
+        _ => Box::new(BufWriter::new(File::create(path)?)),
+    };
+    writer.write_all(bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// tables inside a darc archive are searched entry by entry, as `(entry path, data)`.
+fn sources<'a>(
+    archive: Option<&'a DarcArchive>,
+    buffer: &'a [u8],
+) -> AnyResult<Vec<(Option<String>, &'a [u8])>> {
+    match archive {
+        Some(archive) => Ok(archive
+            .entries()?
+            .into_iter()
+            .map(|entry| {
+                let data = archive.entry_data(&entry.path).unwrap_or_default();
+                (Some(entry.path), data)
+            })
+            .collect()),
+        None => Ok(vec![(None, buffer)]),
+    }
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
-pub enum DataDispatcher {
-    StringTable(StringTable),
-    NameTable(NameTable),
-    FileNameTable(FileNameTable),
+fn parse_archive(buffer: &[u8]) -> AnyResult<Option<DarcArchive>> {
+    Ok(match DarcArchive::is_darc(buffer) {
+        true => Some(DarcArchive::parse(buffer)?),
+        false => None,
+    })
 }
 
-#[derive(Debug, Clone, Copy, Subcommand, ValueEnum)]
-enum DataDispatcherType {
-    StringTable,
-    NameTable,
-    FileNameTable,
+/// patch found in one of the [sources].
+struct FoundPatch<'a> {
+    entry_path: Option<String>,
+    patch: DetectedPatch,
+    /// data of the source the patch was found in.
+    buffer: &'a [u8],
 }
 
-impl DeserializePatch for DataDispatcher {
-    fn deserialize_patch(&self, cursor: &mut Cursor<Vec<u8>>) -> AnyResult<Self> {
-        Ok(match self {
-            DataDispatcher::StringTable(string_table) => {
-                Self::StringTable(string_table.deserialize_patch(cursor)?)
+impl FoundPatch<'_> {
+    fn location(&self) -> String {
+        format!(
+            "`{}` at offset {:#x}{}",
+            String::from_utf8_lossy(self.patch.data.magic_header()),
+            self.patch.offset,
+            self.entry_path
+                .as_ref()
+                .map(|path| format!(" of `{path}`"))
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// tables to look for, given on the command line or listed in the manifest.
+#[derive(Debug, Clone)]
+struct TableQuery {
+    patch_types: Vec<DataDispatcherType>,
+    schemas: Vec<TableSchema>,
+    raw_tables: Vec<RawTableConfig>,
+    /// only the first table of this type is looked for.
+    first_of: Option<DataDispatcherType>,
+}
+
+impl TableQuery {
+    /// the tables listed for the archive in the manifest, every known table if none is.
+    fn from_manifest(archive: &ArchiveManifest) -> AnyResult<Self> {
+        Ok(Self {
+            patch_types: match archive.lists_no_table() {
+                true => DataDispatcherType::ALL.to_vec(),
+                false => archive.tables.clone(),
+            },
+            schemas: archive.load_schemas()?,
+            raw_tables: archive.raw.clone(),
+            first_of: None,
+        })
+    }
+
+    /// every requested table of the sources.
+    fn find_patches<'a>(
+        &self,
+        input: &str,
+        sources: Vec<(Option<String>, &'a [u8])>,
+        encoding: TextEncoding,
+    ) -> AnyResult<Vec<FoundPatch<'a>>> {
+        let mut patches = vec![];
+        for (entry_path, buffer) in sources {
+            let detected = DataDispatcher::detect_all(
+                buffer,
+                &self.patch_types,
+                &self.schemas,
+                &self.raw_tables,
+                encoding,
+            )?;
+            patches.extend(detected.into_iter().map(|patch| FoundPatch {
+                entry_path: entry_path.clone(),
+                patch,
+                buffer,
+            }));
+        }
+        // only the first table of the given type, the patcher splices at the first magic header.
+        if self.first_of.is_some() {
+            patches.truncate(1);
+        }
+
+        if patches.is_empty() {
+            match self.first_of {
+                Some(patch_type) => {
+                    return Err(DispatcherError::HeaderNotFound {
+                        header: String::from_utf8_lossy(patch_type.magic_header()).into_owned(),
+                    }
+                    .into());
+                }
+                None => return Err(Failure::NotFound(input.to_string()).into()),
             }
-            DataDispatcher::NameTable(name_table) => {
-                Self::NameTable(name_table.deserialize_patch(cursor)?)
+        }
+        Ok(patches)
+    }
+}
+
+impl TableArgs {
+    /// no table is given, the ones of the manifest are used.
+    fn is_empty(&self) -> bool {
+        self.patch_type.is_none() && self.schema.is_empty() && self.raw.is_empty()
+    }
+
+    fn encoding(&self, manifest: Option<&Manifest>) -> TextEncoding {
+        source_encoding(self.encoding, manifest)
+    }
+
+    /// the tables given on the command line, every known table if none is.
+    fn query(&self) -> AnyResult<TableQuery> {
+        let schemas = self
+            .schema
+            .iter()
+            .map(|schema| TableSchema::find(schema))
+            .collect::<Result<Vec<_>, _>>()?;
+        let raw_tables = self
+            .raw
+            .iter()
+            .map(|magic| {
+                let mut config = RawTableConfig::new(magic, self.raw_count);
+                config.count_offset = self.raw_count_offset;
+                if let Some(header_length) = self.raw_header_length {
+                    config.header_length = header_length;
+                }
+                config.validate().map(|_| config)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let patch_types = match self.patch_type {
+            Some(patch_type) => vec![patch_type],
+            None if !schemas.is_empty() || !raw_tables.is_empty() => vec![],
+            None => DataDispatcherType::ALL.to_vec(),
+        };
+        Ok(TableQuery {
+            patch_types,
+            schemas,
+            raw_tables,
+            first_of: self.patch_type,
+        })
+    }
+
+    /// the tables to look for in an archive, the ones of the manifest unless tables are
+    /// given on the command line.
+    fn archive_query(&self, archive: Option<&ArchiveManifest>) -> AnyResult<TableQuery> {
+        match archive {
+            Some(archive) if self.is_empty() => TableQuery::from_manifest(archive),
+            _ => self.query(),
+        }
+    }
+}
+
+/// the manifest, for an argument left out that only the manifest can give.
+fn require_manifest<'a>(manifest: Option<&'a Manifest>, argument: &str) -> AnyResult<&'a Manifest> {
+    manifest.ok_or_else(|| {
+        anyhow::anyhow!("give {argument}, or a project manifest (`{MANIFEST_FILE_NAME}`)")
+    })
+}
+
+/// encoding of the original texts, for dumping and inspecting.
+fn source_encoding(encoding: Option<TextEncoding>, manifest: Option<&Manifest>) -> TextEncoding {
+    encoding
+        .or(manifest.map(|manifest| manifest.source_encoding))
+        .unwrap_or_default()
+}
+
+/// encoding of the translated texts, for patching.
+fn target_encoding(encoding: Option<TextEncoding>, manifest: Option<&Manifest>) -> TextEncoding {
+    encoding
+        .or(manifest.map(Manifest::target_encoding))
+        .unwrap_or_default()
+}
+
+/// darc archive of the game, with its entry in the manifest if it is listed there.
+type GameArchive<'a> = (PathBuf, Option<&'a ArchiveManifest>);
+
+/// the game directory and its darc archives, the ones listed in the manifest unless a
+/// directory is given.
+fn game_archives<'a>(
+    input: Option<&str>,
+    manifest: Option<&'a Manifest>,
+) -> AnyResult<(PathBuf, Vec<GameArchive<'a>>)> {
+    let (game_dir, listed) = match input {
+        Some(input) => (PathBuf::from(input), &[][..]),
+        None => {
+            let manifest = require_manifest(manifest, "an input")?;
+            (manifest.game_dir.clone(), manifest.archives.as_slice())
+        }
+    };
+    let archives = match listed.is_empty() {
+        true => darc_paths(&game_dir)?
+            .into_iter()
+            .map(|path| (path, None))
+            .collect(),
+        false => listed
+            .iter()
+            .map(|archive| (archive.path.clone(), Some(archive)))
+            .collect(),
+    };
+    Ok((game_dir, archives))
+}
+
+/// the input read with the tables to look for, or every archive of the manifest.
+fn inspected_inputs(
+    input: Option<&str>,
+    tables: &TableArgs,
+    manifest: Option<&Manifest>,
+) -> AnyResult<Vec<(String, Vec<u8>, TableQuery)>> {
+    match input {
+        Some(input) => Ok(vec![(
+            input.to_string(),
+            read_input(input)?,
+            tables.query()?,
+        )]),
+        None => game_archives(None, manifest)?
+            .1
+            .into_iter()
+            .map(|(path, archive)| {
+                Ok((
+                    path.display().to_string(),
+                    fs::read(&path)?,
+                    tables.archive_query(archive)?,
+                ))
+            })
+            .collect(),
+    }
+}
+
+/// check the patch survives both round trips, then return the ron, json or yaml dump.
+fn dump_patch(
+    patch: &DetectedPatch,
+    buffer: &[u8],
+    encoding: TextEncoding,
+    format: DumpFormat,
+) -> AnyResult<String> {
+    let mismatch = |reason| Failure::Mismatch {
+        header: String::from_utf8_lossy(patch.data.magic_header()).into_owned(),
+        reason,
+    };
+
+    // make sure the dump can be written back without losing anything.
+    let original = &buffer[patch.offset..patch.offset + patch.length];
+    if patch.data.to_bytes(encoding)? != original {
+        bail!(mismatch("rebuilt from the dump"));
+    }
+
+    // make sure the dump can be loaded back for patching.
+    let dump_string = match format {
+        DumpFormat::Json => serde_json::to_string_pretty(&patch.data)?,
+        DumpFormat::Yaml => patch.data.to_yaml_string()?,
+        _ => to_string_pretty(&patch.data, PrettyConfig::default())?,
+    };
+    let loaded = match format {
+        DumpFormat::Json => DataDispatcher::from_json_str(&dump_string)?,
+        DumpFormat::Yaml => DataDispatcher::from_yaml_str(&dump_string)?,
+        _ => DataDispatcher::from_ron_str(&dump_string)?,
+    };
+    if loaded != patch.data {
+        bail!(mismatch("loaded back from the dump"));
+    }
+
+    Ok(dump_string)
+}
+
+/// name of the patch type in output paths, the schema name for schema tables.
+fn type_label(data: &DataDispatcher) -> String {
+    match data.patch_type() {
+        Some(patch_type) => patch_type
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default(),
+        None => data.type_name().to_string(),
+    }
+}
+
+/// output path of the `index`-th patch of a type when several patches are dumped,
+/// e.g. `tblstr.ron` -> `tblstr.string-table.ron`, `tblstr.string-table.1.ron`.
+fn numbered_output(output: &str, type_name: &str, index: usize) -> String {
+    let output = Path::new(output);
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let mut file_name = match index {
+        0 => format!("{stem}.{type_name}"),
+        _ => format!("{stem}.{type_name}.{index}"),
+    };
+    if let Some(extension) = output.extension() {
+        file_name = format!("{file_name}.{}", extension.to_string_lossy());
+    }
+    output
+        .with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// the dump of the patch in `format`, ron, json and yaml dumps are checked to survive both
+/// round trips. the glossary terms found in the texts are noted in the translation formats.
+fn format_dump(
+    patch: &DetectedPatch,
+    buffer: &[u8],
+    encoding: TextEncoding,
+    format: DumpFormat,
+    glossary: &Glossary,
+) -> AnyResult<Vec<u8>> {
+    let xliff = |version| -> AnyResult<Vec<u8>> {
+        let mut xliff = XliffFile::export(&patch.data, version)?;
+        for unit in &mut xliff.units {
+            unit.notes.extend(glossary.notes(&unit.source));
+        }
+        Ok(xliff.to_string().into_bytes())
+    };
+    let sheet = |sheet_format| -> AnyResult<Vec<u8>> {
+        let mut sheet = Sheet::export(&patch.data)?;
+        for row in &mut sheet.rows {
+            append_notes(&mut row.notes, glossary.notes(&row.source));
+        }
+        Ok(sheet.to_bytes(sheet_format)?)
+    };
+    Ok(match format {
+        DumpFormat::Ron | DumpFormat::Json | DumpFormat::Yaml => {
+            dump_patch(patch, buffer, encoding, format)?.into_bytes()
+        }
+        DumpFormat::Po => {
+            let mut po = PoFile::export(&patch.data)?;
+            for entry in &mut po.entries {
+                entry.notes.extend(glossary.notes(&entry.source));
             }
-            DataDispatcher::FileNameTable(fname_table) => {
-                Self::FileNameTable(fname_table.deserialize_patch(cursor)?)
+            po.to_string().into_bytes()
+        }
+        DumpFormat::Xliff => xliff(XliffVersion::V1_2)?,
+        DumpFormat::Xliff2 => xliff(XliffVersion::V2_0)?,
+        DumpFormat::Csv => sheet(SheetFormat::Csv)?,
+        DumpFormat::Tsv => sheet(SheetFormat::Tsv)?,
+        DumpFormat::Xlsx => sheet(SheetFormat::Xlsx)?,
+        DumpFormat::Project => {
+            let mut project = TranslationProject::export(&patch.data)?;
+            for entry in &mut project.entries {
+                append_notes(&mut entry.notes, glossary.notes(&entry.original));
             }
-        })
+            project.to_json_string()?.into_bytes()
+        }
+    })
+}
+
+/// append `more` to the notes of a single text field, separated by `; `.
+fn append_notes(notes: &mut String, more: Vec<String>) {
+    for note in more {
+        if !notes.is_empty() {
+            notes.push_str("; ");
+        }
+        notes.push_str(&note);
     }
 }
 
-/// item in string table patch:
-///
-/// ```{text}
-/// 0                   1
-/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-/// |   ID  |LEN|                   |
-/// +-+-+-+-+-+-+                   +
-/// |              DATA             |
-/// +                         +-+-+-+
-/// |                         | PAD |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-///
-/// 0-3: id, u32 little-endian;
-/// 4-5: length, u16 little-endian;
-/// 6-: data, string (length bytes, padding to 2 bytes alignment);
-/// ```
-///
-/// ## Note
-/// the actual content of the string needs to be obtained by bitwise negation.
-/// the end of the string can be identified by the following characteristics：
-/// - string ends with a LF (\n): 0xF5 0xFF
-/// - string ends with a null (\0): 0xFF 0xFF
-#[derive(Debug, Default, Serialize, PartialEq, Eq, Clone)]
-struct StringTableItem {
-    id: u32,
-    length: u16,
-    data: String,
-}
-
-impl DeserializePatch for StringTableItem {
-    fn deserialize_patch(&self, cursor: &mut Cursor<Vec<u8>>) -> AnyResult<Self> {
-        let mut string_table_item = Self::default();
-
-        // id: u32, little-endian <4 bytes>
-        let mut id_bytes = [0; 4];
-        cursor.read_exact(&mut id_bytes)?;
-        string_table_item.id = u32::from_le_bytes(id_bytes);
-
-        // length: u16, little-endian <2 byte>
-        let mut length_bytes = [0; 2];
-        cursor.read_exact(&mut length_bytes)?;
-        string_table_item.length = u16::from_le_bytes(length_bytes);
-
-        // data: string (length bytes, padding to 4 bytes alignment)
-        //
-        // NOTE:
-        // the actual content of the string needs to be obtained by bitwise negation.
-        // and the padding can be ignored by [String::from_utf8] automatically.
-        let mut raw_data = vec![0; string_table_item.length as usize];
-        cursor.read_exact(&mut raw_data)?;
-        raw_data.iter_mut().for_each(|byte| *byte = !*byte);
-        let (string, _, _) = SHIFT_JIS.decode(&raw_data);
-        string_table_item.data = string.to_string();
-
-        Ok(string_table_item)
-    }
-}
-
-/// structure of string table patch:
-///
-/// ```{text}
-/// 0                   1
-/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-/// |[|S|T|R|T|B|L|]|  CNT  |  UNK  |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-/// |   ID  |   L   |   U   |       |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+       +
-/// |                               |
-/// +              DATA             +
-/// |                               |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-///
-/// 0-7: header, `[STRTBL]`;
-/// 8-11: item_count, u32 little-endian;
-/// 12-15: unknown (assume as magic number), u32 little-endian;
-/// 16-: item, [StringTableItem];
-/// ```
-#[derive(Debug, Default, Serialize, PartialEq, Eq, Clone)]
-pub struct StringTable {
-    item_count: u32,
-    assume_magic_number: u32,
-    items: Vec<StringTableItem>,
-}
-
-impl DataDispatcherHeader for StringTable {
-    const MAGIC_HEADER: &[u8] = b"[STRTBL]";
-}
-
-impl DeserializePatch for StringTable {
-    fn deserialize_patch(&self, cursor: &mut Cursor<Vec<u8>>) -> AnyResult<Self> {
-        // header: string <8 bytes>
-        cursor.seek(SeekFrom::Current(8))?;
-
-        // item_count: u32, little-endian <4 bytes>
-        let mut item_count_bytes = [0; 4];
-        cursor.read_exact(&mut item_count_bytes)?;
-        let item_count = u32::from_le_bytes(item_count_bytes);
-
-        // unknown (assume as magic number): u32, little-endian <4 bytes>
-        let mut assume_magic_number_bytes = [0; 4];
-        cursor.read_exact(&mut assume_magic_number_bytes)?;
-        let assume_magic_number = u32::from_le_bytes(assume_magic_number_bytes);
-
-        // item: [StringTableItem]
-        let mut items = vec![];
-        for _ in 0..item_count {
-            items.push(StringTableItem::default().deserialize_patch(cursor)?);
+/// the glossaries given on the command line, or else the ones of the manifest.
+fn load_glossary(paths: &[PathBuf], manifest: Option<&Manifest>) -> AnyResult<Glossary> {
+    let paths = match (paths, manifest) {
+        ([_, ..], _) | ([], None) => paths,
+        ([], Some(manifest)) => &manifest.glossaries[..],
+    };
+    Ok(Glossary::load_all(paths)?)
+}
+
+/// the table of a dump, to be patched over the `index`-th table of its type in `container`,
+/// with a warning for every translation matching no item.
+fn load_dump(
+    path: &Path,
+    format: DumpFormat,
+    container: &[u8],
+    encoding: TextEncoding,
+    index: usize,
+) -> AnyResult<DataDispatcher> {
+    let (data, summary) = DataDispatcher::load_patch(path, format, container, encoding, index)?;
+    for key in &summary.unknown_keys {
+        eprintln!(
+            "warning: {}: no item `{key}` in the table, skipped",
+            path.display()
+        );
+    }
+    Ok(data)
+}
+
+/// write the dump of every patch to `output`, or to paths numbered by table type if
+/// `numbered`, and return the paths written.
+fn dump_patches(
+    patches: &[FoundPatch],
+    output: &str,
+    encoding: TextEncoding,
+    numbered: bool,
+    format: DumpFormat,
+    glossary: &Glossary,
+) -> AnyResult<Vec<String>> {
+    let mut outputs = vec![];
+    let mut type_counts = HashMap::new();
+    for found in patches {
+        let patch = &found.patch;
+        let output = if numbered {
+            let type_name = type_label(&patch.data);
+            let type_count = type_counts.entry(type_name.clone()).or_insert(0);
+            *type_count += 1;
+            numbered_output(output, &type_name, *type_count - 1)
+        } else {
+            output.to_string()
+        };
+
+        // tables the format can't hold are left out.
+        if !format.supports(&patch.data) {
+            continue;
+        }
+        for issue in patch.data.decode_issues(patch.offset as u64) {
+            eprintln!("warning: {issue}, kept as escape characters");
         }
 
-        Ok(Self {
-            item_count,
-            assume_magic_number,
-            items,
-        })
+        let dump_bytes = format_dump(patch, found.buffer, encoding, format, glossary)?;
+        write_output(&output, &dump_bytes)?;
+        outputs.push(output);
     }
+    Ok(outputs)
 }
 
-/// item in name table patch:
-///
-/// ```{text}
-/// 0                   1
-/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-/// |LEN|            DATA           |
-/// +-+-+                     +-+-+-+
-/// |                         | PAD |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-///
-/// 0-1: length, u16 little-endian;
-/// 2-: data, string (length bytes, padding to 2 bytes alignment);
-/// ```
-#[derive(Debug, Default, Serialize, PartialEq, Eq, Clone)]
-struct NameTableItem {
-    length: u16,
-    data: String,
-}
-
-impl DeserializePatch for NameTableItem {
-    fn deserialize_patch(&self, cursor: &mut Cursor<Vec<u8>>) -> AnyResult<Self> {
-        let mut name_table_item = Self::default();
-
-        // length: u16, little-endian <2 byte>
-        let mut length_bytes = [0; 2];
-        cursor.read_exact(&mut length_bytes)?;
-        name_table_item.length = u16::from_le_bytes(length_bytes);
-
-        // data: string (length bytes, padding to 4 bytes alignment)
-        //
-        // NOTE:
-        // the padding can be ignored by [String::from_utf8] automatically.
-        let mut raw_data = vec![0; name_table_item.length as usize];
-        cursor.read_exact(&mut raw_data)?;
-        let (string, _, _) = SHIFT_JIS.decode(&raw_data);
-        name_table_item.data = string.to_string();
-
-        Ok(name_table_item)
-    }
-}
-
-/// structure of name table patch:
-///
-/// ```{text}
-/// 0                   1
-/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-/// |[|M|E|S|N|A|M|]|UNK|CNT|LEN|   |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-/// |    DATA   |LEN|      DATA     |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-///
-/// 0-7: header, `[MESNAM]`;
-/// 8-9: unknown (assume as padding), u16 little-endian;
-/// 10-11: item_count, u16 little-endian;
-/// 12-: item, [NameTableItem];
-/// ```
-#[derive(Debug, Default, Serialize, PartialEq, Eq, Clone)]
-pub struct NameTable {
-    assume_padding: u16,
-    item_count: u16,
-    items: Vec<NameTableItem>,
-}
-
-impl DataDispatcherHeader for NameTable {
-    const MAGIC_HEADER: &[u8] = b"[MESNAM]";
-}
-
-impl DeserializePatch for NameTable {
-    fn deserialize_patch(&self, cursor: &mut Cursor<Vec<u8>>) -> AnyResult<Self> {
-        // header: string <8 bytes>
-        cursor.seek(SeekFrom::Current(8))?;
-
-        // unknown (assume as padding): u16, little-endian <2 bytes>
-        let mut assume_padding_bytes = [0; 2];
-        cursor.read_exact(&mut assume_padding_bytes)?;
-        let assume_padding = u16::from_le_bytes(assume_padding_bytes);
-
-        // item_count: u16, little-endian <2 bytes>
-        let mut item_count_bytes = [0; 2];
-        cursor.read_exact(&mut item_count_bytes)?;
-        let item_count = u16::from_le_bytes(item_count_bytes);
-
-        // item: [NameTableItem]
-        let mut items = vec![];
-        for _ in 0..item_count {
-            items.push(NameTableItem::default().deserialize_patch(cursor)?);
+fn dump(args: DumpArgs, manifest: Option<&Manifest>) -> AnyResult<()> {
+    let Some(input) = &args.input else {
+        return dump_all(
+            DumpAllArgs {
+                input: None,
+                output: args.output,
+                format: args.format.into_iter().collect(),
+                glossary: args.glossary,
+                tables: args.tables,
+            },
+            manifest,
+        );
+    };
+    let Some(output) = &args.output else {
+        bail!("give an output path to dump `{input}`");
+    };
+    let encoding = args.tables.encoding(manifest);
+    let buffer = read_input(input)?;
+    let archive = parse_archive(&buffer)?;
+    let mut patches =
+        args.tables
+            .query()?
+            .find_patches(input, sources(archive.as_ref(), &buffer)?, encoding)?;
+    let format = match (args.format, output.as_str()) {
+        (Some(format), _) => format,
+        (None, "-") => DumpFormat::Ron,
+        (None, output) => DumpFormat::from_path(output),
+    };
+    patches.retain(|found| format.supports(&found.patch.data));
+    if patches.is_empty() {
+        bail!(
+            "no table of `{input}` can be dumped as {}",
+            format.extension()
+        );
+    }
+
+    let single = patches.len() == 1;
+    if !single && output == "-" {
+        bail!(
+            "{} tables found in `{input}`, give an output path or a patch type to dump them",
+            patches.len(),
+        );
+    }
+    let glossary = load_glossary(&args.glossary, manifest)?;
+    let outputs = dump_patches(&patches, output, encoding, !single, format, &glossary)?;
+    if !single {
+        for (found, output) in patches.iter().zip(outputs) {
+            eprintln!("{} -> {output}", found.location());
         }
+    }
 
-        Ok(Self {
-            assume_padding,
-            item_count,
-            items,
-        })
+    Ok(())
+}
+
+/// processed, skipped and failed files of a batch run.
+#[derive(Debug, Default)]
+struct BatchSummary {
+    processed: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+impl BatchSummary {
+    fn processed(&mut self, path: &Path, detail: &str) {
+        self.processed += 1;
+        println!("processed: {}: {detail}", path.display());
     }
+
+    fn skipped(&mut self, path: &Path, reason: &str) {
+        self.skipped += 1;
+        println!("skipped: {}: {reason}", path.display());
+    }
+
+    fn failed(&mut self, path: &Path, e: &AnyError) {
+        self.failed += 1;
+        println!("failed: {}: {e:#}", path.display());
+    }
+
+    /// print the totals, an error if any file failed.
+    fn report(self) -> AnyResult<()> {
+        println!(
+            "{} processed, {} skipped, {} failed",
+            self.processed, self.skipped, self.failed
+        );
+        match self.failed {
+            0 => Ok(()),
+            failed => Err(Failure::BatchFailed { failed }.into()),
+        }
+    }
+}
+
+/// output path mirroring `path` of the `input` tree into the `output` tree.
+fn mirrored_path(input: &Path, path: &Path, output: &Path) -> PathBuf {
+    output.join(path.strip_prefix(input).unwrap_or(path))
 }
 
-/// item in file name table patch:
-/// ```{text}
-/// 0                   1
-/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-/// |LEN|            DATA           |
-/// +-+-+                     +-+-+-+
-/// |                         | PAD |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-///
-/// 0-1: length, u16 little-endian;
-/// 2-: data, string (length bytes, padding to 2 bytes alignment);
-/// ```
-///
-/// ## Note
-/// the actual content of the string needs to be obtained by bitwise negation.
-#[derive(Debug, Default, Serialize, PartialEq, Eq, Clone)]
-struct FileNameTableItem {
-    length: u16,
-    data: String,
-}
-
-impl DeserializePatch for FileNameTableItem {
-    fn deserialize_patch(&self, cursor: &mut Cursor<Vec<u8>>) -> AnyResult<Self> {
-        let mut file_name_table_item = Self::default();
-
-        // length: u16, little-endian <2 byte>
-        let mut length_bytes = [0; 2];
-        cursor.read_exact(&mut length_bytes)?;
-        file_name_table_item.length = u16::from_le_bytes(length_bytes);
-
-        // data: string (length bytes, padding to 4 bytes alignment)
-        //
-        // NOTE:
-        // the actual content of the string needs to be obtained by bitwise negation.
-        // and the padding can be ignored by [String::from_utf8] automatically.
-        let mut raw_data = vec![0; file_name_table_item.length as usize];
-        cursor.read_exact(&mut raw_data)?;
-        raw_data.iter_mut().for_each(|byte| *byte = !*byte);
-        let (string, _, _) = SHIFT_JIS.decode(&raw_data);
-        file_name_table_item.data = string.to_string();
-
-        Ok(file_name_table_item)
-    }
-}
-
-/// structure of file name table patch:
-///
-/// ```{text}
-/// 0                   1
-/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-/// |[|F|-|N|A|M|E|]|  CNT  |  UNK  |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-/// |LEN|    DATA   |LEN|    DATA   |
-/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-///
-/// 0-7: header, `[F-NAME]`;
-/// 8-11: item_count, u32 little-endian;
-/// 12-15: unknown (assume as magic number), u32 little-endian;
-/// 16-: item, [FileNameTableItem];
-/// ```
-#[derive(Debug, Default, Serialize, PartialEq, Eq, Clone)]
-pub struct FileNameTable {
-    item_count: u32,
-    assume_magic_number: u32,
-    items: Vec<FileNameTableItem>,
-}
-
-impl DataDispatcherHeader for FileNameTable {
-    const MAGIC_HEADER: &[u8] = b"[F-NAME]";
-}
-
-impl DeserializePatch for FileNameTable {
-    fn deserialize_patch(&self, cursor: &mut Cursor<Vec<u8>>) -> AnyResult<Self> {
-        // header: string <8 bytes>
-        cursor.seek(SeekFrom::Current(8))?;
-
-        // item_count: u32, little-endian <4 bytes>
-        let mut item_count_bytes = [0; 4];
-        cursor.read_exact(&mut item_count_bytes)?;
-        let item_count = u32::from_le_bytes(item_count_bytes);
-
-        // unknown (assume as magic number): u32, little-endian <4 bytes>
-        let mut assume_magic_number_bytes = [0; 4];
-        cursor.read_exact(&mut assume_magic_number_bytes)?;
-        let assume_magic_number = u32::from_le_bytes(assume_magic_number_bytes);
-
-        // item: [FileNameTableItem]
-        let mut items = vec![];
-        for _ in 0..item_count {
-            items.push(FileNameTableItem::default().deserialize_patch(cursor)?);
+/// dump every table of every darc archive of the game directory into a mirrored tree,
+/// as `<archive>.<table type>[.<index>].<format>`.
+fn dump_all(args: DumpAllArgs, manifest: Option<&Manifest>) -> AnyResult<()> {
+    let (game_dir, archives) = game_archives(args.input.as_deref(), manifest)?;
+    let output = match &args.output {
+        Some(output) => PathBuf::from(output),
+        None => require_manifest(manifest, "an output directory")?
+            .dump_dir
+            .clone(),
+    };
+    let formats = match (&args.format[..], manifest) {
+        ([_, ..], _) => args.format.clone(),
+        ([], Some(manifest)) => manifest.formats.clone(),
+        ([], None) => vec![DumpFormat::Ron],
+    };
+    let encoding = args.tables.encoding(manifest);
+    let glossary = load_glossary(&args.glossary, manifest)?;
+
+    let mut summary = BatchSummary::default();
+    for (path, archive_manifest) in archives {
+        let dump_archive = || -> AnyResult<Option<usize>> {
+            let buffer = fs::read(&path)?;
+            let archive = parse_archive(&buffer)?;
+            let label = path.display().to_string();
+            let query = args.tables.archive_query(archive_manifest)?;
+            let patches =
+                match query.find_patches(&label, sources(archive.as_ref(), &buffer)?, encoding) {
+                    Ok(patches) => patches,
+                    Err(e) if exit_code(&e) == EXIT_NOT_FOUND => return Ok(None),
+                    Err(e) => return Err(e),
+                };
+
+            let dump_path = mirrored_path(&game_dir, &path, &output);
+            if let Some(parent) = dump_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            for &format in &formats {
+                let mut format_path = dump_path.clone().into_os_string();
+                format_path.push(format!(".{}", format.extension()));
+                dump_patches(
+                    &patches,
+                    &format_path.to_string_lossy(),
+                    encoding,
+                    true,
+                    format,
+                    &glossary,
+                )?;
+            }
+            Ok(Some(patches.len()))
+        };
+        match dump_archive() {
+            Ok(Some(tables)) => summary.processed(&path, &format!("{tables} tables dumped")),
+            Ok(None) => summary.skipped(&path, "no table found"),
+            Err(e) => summary.failed(&path, &e),
         }
+    }
+    summary.report()
+}
 
-        Ok(Self {
-            item_count,
-            assume_magic_number,
-            items,
-        })
+/// dumps of the archive written by [dump_all], with the index of their table among the
+/// tables of the same type.
+fn archive_dumps(
+    dump_dir: &Path,
+    archive_name: &str,
+    format: DumpFormat,
+) -> AnyResult<Vec<(PathBuf, usize)>> {
+    if !dump_dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let prefix = format!("{archive_name}.");
+    let suffix = format!(".{}", format.extension());
+    let mut dumps = vec![];
+    for entry in fs::read_dir(dump_dir)? {
+        let path = entry?.path();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let Some(table) = file_name
+            .strip_prefix(&prefix)
+            .and_then(|name| name.strip_suffix(&suffix))
+        else {
+            continue;
+        };
+        let index = table
+            .rsplit_once('.')
+            .and_then(|(_, index)| index.parse().ok())
+            .unwrap_or(0);
+        dumps.push((path, index));
     }
+    dumps.sort();
+    Ok(dumps)
 }
 
-fn main() -> AnyResult<()> {
-    let args = ConsoleArgs::parse();
+/// patch the dumps of the mirrored tree back into the archives of the game directory, only
+/// the archives that changed are written to the output tree.
+fn patch_all(args: PatchAllArgs, manifest: Option<&Manifest>) -> AnyResult<()> {
+    let (game_dir, archives) = game_archives(args.container.as_deref(), manifest)?;
+    let dump_dir = match &args.input {
+        Some(input) => PathBuf::from(input),
+        None => require_manifest(manifest, "a dump directory")?
+            .dump_dir
+            .clone(),
+    };
+    let output = match &args.output {
+        Some(output) => PathBuf::from(output),
+        None => require_manifest(manifest, "an output directory")?
+            .patched_dir
+            .clone(),
+    };
+    let format = manifest.map(Manifest::patch_format).unwrap_or_default();
+    let encoding = target_encoding(args.encoding, manifest);
+
+    let mut summary = BatchSummary::default();
+    for (path, _) in archives {
+        let patch_archive = || -> AnyResult<Option<usize>> {
+            let dump_path = mirrored_path(&game_dir, &path, &dump_dir);
+            let archive_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let dumps = archive_dumps(
+                dump_path.parent().unwrap_or(Path::new(".")),
+                &archive_name,
+                format,
+            )?;
 
-    let file = File::open(&args.input)?;
-    let mut buffer = vec![0; file.metadata()?.len() as usize];
-    let mut reader = BufReader::new(file);
-
-    reader.read_exact(&mut buffer)?;
-
-    let mut writer = BufWriter::new(File::create(args.output)?);
-
-    let (data_patcher, header) = match args.patch_type {
-        DataDispatcherType::StringTable => (
-            DataDispatcher::StringTable(StringTable::default()),
-            StringTable::MAGIC_HEADER,
-        ),
-        DataDispatcherType::NameTable => (
-            DataDispatcher::NameTable(NameTable::default()),
-            NameTable::MAGIC_HEADER,
-        ),
-        DataDispatcherType::FileNameTable => (
-            DataDispatcher::FileNameTable(FileNameTable::default()),
-            FileNameTable::MAGIC_HEADER,
-        ),
+            let original = fs::read(&path)?;
+            let mut patched = original.clone();
+            for (dump, index) in &dumps {
+                let data = load_dump(dump, format, &patched, encoding, *index)?;
+                patched = data.patch_nth(&patched, encoding, *index)?;
+            }
+            if patched == original {
+                return Ok(None);
+            }
+
+            let output = mirrored_path(&game_dir, &path, &output);
+            if let Some(parent) = output.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(output, patched)?;
+            Ok(Some(dumps.len()))
+        };
+        match patch_archive() {
+            Ok(Some(tables)) => summary.processed(&path, &format!("{tables} tables patched")),
+            Ok(None) => summary.skipped(&path, "no edited table"),
+            Err(e) => summary.failed(&path, &e),
+        }
+    }
+    summary.report()
+}
+
+fn patch(args: PatchArgs, manifest: Option<&Manifest>) -> AnyResult<()> {
+    if args.input == "-" && args.container == "-" {
+        bail!("the dump and the container can not both be read from stdin");
+    }
+    let container = read_input(&args.container)?;
+    let encoding = target_encoding(args.encoding, manifest);
+    let data = match args.input.as_str() {
+        "-" => DataDispatcher::from_ron_str(&String::from_utf8(read_input("-")?)?)?,
+        path => load_dump(
+            Path::new(path),
+            DumpFormat::from_path(path),
+            &container,
+            encoding,
+            0,
+        )?,
     };
 
-    let start_pos = buffer
-        .windows(header.len())
-        .position(|window| window == header)
-        .expect(&format!(
-            "header `{}` not found",
-            String::from_utf8_lossy(header)
-        ));
-    let mut cursor = Cursor::new(buffer);
-    cursor.seek(SeekFrom::Start(start_pos as u64))?;
+    let patched = data.patch(&container, encoding)?;
+    write_output(&args.output, &patched)
+}
+
+fn info(args: InspectArgs, manifest: Option<&Manifest>) -> AnyResult<()> {
+    let encoding = args.tables.encoding(manifest);
+    for (input, buffer, query) in inspected_inputs(args.input.as_deref(), &args.tables, manifest)? {
+        let archive = parse_archive(&buffer)?;
+        let patches = query.find_patches(&input, sources(archive.as_ref(), &buffer)?, encoding)?;
+
+        for found in &patches {
+            let data = &found.patch.data;
+            println!(
+                "{} {}: {:#x} bytes, {} items",
+                found.location(),
+                data.type_name(),
+                found.patch.length,
+                data.len()
+            );
+            for (name, value) in data.header_fields() {
+                println!("    {name}: {value}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn verify(args: InspectArgs, manifest: Option<&Manifest>) -> AnyResult<()> {
+    let encoding = args.tables.encoding(manifest);
+    let mut failed = 0;
+    let mut total = 0;
+    for (input, buffer, query) in inspected_inputs(args.input.as_deref(), &args.tables, manifest)? {
+        let archive = parse_archive(&buffer)?;
+        let patches = query.find_patches(&input, sources(archive.as_ref(), &buffer)?, encoding)?;
+
+        total += patches.len();
+        for found in &patches {
+            let verified = [DumpFormat::Ron, DumpFormat::Json, DumpFormat::Yaml]
+                .into_iter()
+                .try_for_each(|format| {
+                    dump_patch(&found.patch, found.buffer, encoding, format).map(drop)
+                });
+            match verified {
+                Ok(()) => println!("ok: {}", found.location()),
+                Err(e) => {
+                    failed += 1;
+                    println!("failed: {}: {e:#}", found.location());
+                }
+            }
+        }
+    }
 
-    let data = data_patcher.deserialize_patch(&mut cursor)?;
+    match failed {
+        0 => Ok(()),
+        _ => Err(Failure::VerifyFailed { failed, total }.into()),
+    }
+}
+
+/// finding of [validate] with the file and darc entry it was found in.
+#[derive(Serialize)]
+struct FileFinding {
+    file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry: Option<String>,
+    #[serde(flatten)]
+    finding: data_dispatcher::Finding,
+}
+
+/// the input, or every archive of the manifest followed by its patched counterpart.
+fn validated_inputs(input: Option<&str>, manifest: Option<&Manifest>) -> AnyResult<Vec<String>> {
+    if let Some(input) = input {
+        return Ok(vec![input.to_string()]);
+    }
+    let (game_dir, archives) = game_archives(None, manifest)?;
+    let patched_dir = &require_manifest(manifest, "an input")?.patched_dir;
+    let mut inputs = vec![];
+    for (path, _) in archives {
+        let patched = mirrored_path(&game_dir, &path, patched_dir);
+        inputs.push(path.display().to_string());
+        if patched.is_file() {
+            inputs.push(patched.display().to_string());
+        }
+    }
+    Ok(inputs)
+}
+
+fn validate_input(args: ValidateArgs, manifest: Option<&Manifest>) -> AnyResult<()> {
+    let mut findings = vec![];
+    for input in validated_inputs(args.input.as_deref(), manifest)? {
+        let buffer = read_input(&input)?;
+        let archive = parse_archive(&buffer)?;
+        for (entry, buffer) in sources(archive.as_ref(), &buffer)? {
+            findings.extend(validate(buffer).into_iter().map(|finding| FileFinding {
+                file: input.clone(),
+                entry: entry.clone(),
+                finding,
+            }));
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&findings)?);
+    } else {
+        for found in &findings {
+            let entry = found
+                .entry
+                .as_ref()
+                .map(|entry| format!(":{entry}"))
+                .unwrap_or_default();
+            println!(
+                "{}: {}{entry}: {}",
+                found.finding.severity, found.file, found.finding
+            );
+        }
+    }
 
-    writer.write_all(to_string_pretty(&data, PrettyConfig::default())?.as_bytes())?;
+    let errors = findings
+        .iter()
+        .filter(|found| found.finding.severity == Severity::Error)
+        .count();
+    match errors {
+        0 => Ok(()),
+        _ => Err(Failure::Invalid { errors }.into()),
+    }
+}
 
+fn extract(args: ExtractArgs) -> AnyResult<()> {
+    let buffer = read_input(&args.input)?;
+    for entry in DarcArchive::parse(&buffer)?.extract(&args.output)? {
+        println!(
+            "{:#010x} {:#010x} {}",
+            entry.offset, entry.length, entry.path
+        );
+    }
     Ok(())
 }
+
+fn repack(args: RepackArgs) -> AnyResult<()> {
+    write_output(
+        &args.output,
+        &DarcArchive::from_dir(&args.input)?.to_bytes()?,
+    )
+}
+
+/// `path` itself if it is a file, else every darc archive under it.
+fn darc_paths(path: &Path) -> AnyResult<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut children = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    children.sort();
+
+    let mut paths = vec![];
+    for child in children {
+        if child.is_dir() {
+            paths.extend(darc_paths(&child)?);
+        } else if child
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("darc"))
+        {
+            paths.push(child);
+        }
+    }
+    Ok(paths)
+}
+
+/// print the magic headers found in the buffer, entry by entry for darc archives, and
+/// return how many were found.
+fn scan_buffer(label: &str, buffer: &[u8], encoding: TextEncoding) -> AnyResult<usize> {
+    let archive = parse_archive(buffer)?;
+    let mut found = 0;
+    for (entry_path, buffer) in sources(archive.as_ref(), buffer)? {
+        let source = match entry_path {
+            Some(entry_path) => format!("{label}:{entry_path}"),
+            None => label.to_string(),
+        };
+        for hit in scan(buffer, encoding) {
+            found += 1;
+            let known = hit
+                .known
+                .and_then(|patch_type| patch_type.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            println!("{source}: {:#010x} `{}` {known}", hit.offset, hit.magic);
+            let counts = hit
+                .counts
+                .iter()
+                .map(|count| count.to_string())
+                .collect::<Vec<_>>();
+            if !counts.is_empty() {
+                println!("    counts: {}", counts.join(", "));
+            }
+            match hit.layout {
+                Some(layout) => println!("    layout: {layout}"),
+                None => println!("    layout: no length-prefixed string layout fits"),
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn scan_input(args: ScanArgs, manifest: Option<&Manifest>) -> AnyResult<()> {
+    let encoding = source_encoding(args.encoding, manifest);
+    let mut found = 0;
+    let input = match args.input.as_deref() {
+        Some("-") => {
+            found += scan_buffer("-", &read_input("-")?, encoding)?;
+            "-".to_string()
+        }
+        input => {
+            let (game_dir, archives) = game_archives(input, manifest)?;
+            for (path, _) in archives {
+                let label = path.display().to_string();
+                found += scan_buffer(&label, &fs::read(&path)?, encoding)?;
+            }
+            game_dir.display().to_string()
+        }
+    };
+
+    match found {
+        0 => Err(Failure::NotFound(input).into()),
+        _ => Ok(()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args = ConsoleArgs::parse();
+
+    let result = Manifest::locate(args.manifest.as_deref())
+        .map_err(AnyError::from)
+        .and_then(|manifest| {
+            let manifest = manifest.as_ref();
+            match args.command {
+                Command::Dump(args) => dump(args, manifest),
+                Command::Patch(args) => patch(args, manifest),
+                Command::Info(args) => info(args, manifest),
+                Command::Verify(args) => verify(args, manifest),
+                Command::Validate(args) => validate_input(args, manifest),
+                Command::Extract(args) => extract(args),
+                Command::Repack(args) => repack(args),
+                Command::Scan(args) => scan_input(args, manifest),
+                Command::DumpAll(args) => dump_all(args, manifest),
+                Command::PatchAll(args) => patch_all(args, manifest),
+            }
+        });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
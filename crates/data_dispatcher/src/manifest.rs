@@ -0,0 +1,383 @@
+use crate::{
+    DataDispatcher, DataDispatcherType, DispatcherError, DispatcherResult, RawTableConfig,
+    TableSchema, text::TextEncoding,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// file name of the project manifest, looked up in the working directory.
+pub const MANIFEST_FILE_NAME: &str = "imojiru.toml";
+
+/// format of the dumps written for the translators.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DumpFormat {
+    /// the ron dump of the whole table, which can be patched back as is. json and yaml dumps
+    /// are patched back the same way.
+    #[default]
+    Ron,
+    /// gettext po file of the texts, see [crate::PoFile].
+    Po,
+    /// xliff 1.2 file of the texts for the CAT tools, see [crate::XliffFile].
+    Xliff,
+    /// xliff 2.0 file of the texts for the CAT tools.
+    Xliff2,
+    /// spreadsheet of the string table texts, see [crate::Sheet].
+    Csv,
+    Tsv,
+    Xlsx,
+    /// the json dump of the whole table, with the schema of the ron dump.
+    Json,
+    /// the yaml dump of the whole table, with the schema of the ron dump.
+    Yaml,
+    /// bilingual translation project of the texts, see [crate::TranslationProject].
+    Project,
+}
+
+impl DumpFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            DumpFormat::Ron => "ron",
+            DumpFormat::Po => "po",
+            DumpFormat::Xliff => "xlf",
+            DumpFormat::Xliff2 => "xliff",
+            DumpFormat::Csv => "csv",
+            DumpFormat::Tsv => "tsv",
+            DumpFormat::Xlsx => "xlsx",
+            DumpFormat::Json => "json",
+            DumpFormat::Yaml => "yaml",
+            DumpFormat::Project => "project",
+        }
+    }
+
+    /// the format can hold the table: ron holds every table, the translation formats the
+    /// texts of the known tables, and the spreadsheets those of the string tables only.
+    pub fn supports(self, data: &DataDispatcher) -> bool {
+        match self {
+            DumpFormat::Ron | DumpFormat::Json | DumpFormat::Yaml => true,
+            DumpFormat::Po | DumpFormat::Xliff | DumpFormat::Xliff2 | DumpFormat::Project => {
+                data.patch_type().is_some()
+            }
+            DumpFormat::Csv | DumpFormat::Tsv | DumpFormat::Xlsx => {
+                data.patch_type() == Some(DataDispatcherType::StringTable)
+            }
+        }
+    }
+
+    /// format of a dump file by its extension, ron if the extension is unknown.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        let extension = path.as_ref().extension().unwrap_or_default();
+        match extension.to_string_lossy().to_ascii_lowercase().as_str() {
+            "po" | "pot" => DumpFormat::Po,
+            "xlf" => DumpFormat::Xliff,
+            "xliff" => DumpFormat::Xliff2,
+            "csv" => DumpFormat::Csv,
+            "tsv" | "tab" => DumpFormat::Tsv,
+            "xlsx" => DumpFormat::Xlsx,
+            "json" => DumpFormat::Json,
+            "yaml" | "yml" => DumpFormat::Yaml,
+            "project" => DumpFormat::Project,
+            _ => DumpFormat::Ron,
+        }
+    }
+}
+
+/// project file describing the game files and where the dumps and patched files go.
+///
+/// ```{toml}
+/// game_dir = "game"
+/// dump_dir = "dump"
+/// patched_dir = "patched"
+/// source_encoding = "cp932"
+/// target_encoding = "gbk"
+/// formats = ["ron"]
+/// glossaries = ["glossary/characters.csv"]
+///
+/// [[archives]]
+/// path = "data/script.darc"
+/// tables = ["string-table", "name-table"]
+///
+/// [[archives]]
+/// path = "data/menu.darc"
+/// schemas = ["schemas/menu.ron"]
+/// raw = [{ magic = "[MENUTX]", count = "u16", count_offset = 2, header_length = 4 }]
+/// ```
+///
+/// relative paths are relative to the directory of the manifest, archive paths are
+/// relative to `game_dir`. they are resolved by [Manifest::load].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// directory of the original game files.
+    pub game_dir: PathBuf,
+    /// directory of the dumps, mirroring `game_dir`.
+    #[serde(default = "default_dump_dir")]
+    pub dump_dir: PathBuf,
+    /// directory of the patched archives, mirroring `game_dir`.
+    #[serde(default = "default_patched_dir")]
+    pub patched_dir: PathBuf,
+    /// encoding of the original texts, used for dumping.
+    #[serde(default)]
+    pub source_encoding: TextEncoding,
+    /// encoding of the translated texts, used for patching. the source encoding if omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_encoding: Option<TextEncoding>,
+    /// formats the dumps are written in, the first one is read back when patching.
+    #[serde(default = "default_formats")]
+    pub formats: Vec<DumpFormat>,
+    /// glossaries shared by the translators, their terms are noted in the translation dumps.
+    /// see [crate::Glossary].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub glossaries: Vec<PathBuf>,
+    /// archives holding the tables, every darc archive of `game_dir` if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archives: Vec<ArchiveManifest>,
+}
+
+/// darc archive of the game and the tables it holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArchiveManifest {
+    pub path: PathBuf,
+    /// known table types, every known table type if no table is listed at all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tables: Vec<DataDispatcherType>,
+    /// schema files or built-in schema names.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schemas: Vec<String>,
+    /// tables the crate doesn't know, read as u16 length-prefixed strings.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw: Vec<RawTableConfig>,
+}
+
+fn default_dump_dir() -> PathBuf {
+    PathBuf::from("dump")
+}
+
+fn default_patched_dir() -> PathBuf {
+    PathBuf::from("patched")
+}
+
+fn default_formats() -> Vec<DumpFormat> {
+    vec![DumpFormat::Ron]
+}
+
+impl Manifest {
+    /// the manifest at `path`, or else [MANIFEST_FILE_NAME] of the working directory if
+    /// there is one.
+    pub fn locate(path: Option<&Path>) -> DispatcherResult<Option<Self>> {
+        match path {
+            Some(path) => Ok(Some(Self::load(path)?)),
+            None if Path::new(MANIFEST_FILE_NAME).is_file() => {
+                Ok(Some(Self::load(MANIFEST_FILE_NAME)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// load the manifest and resolve its paths against its directory.
+    pub fn load<P: AsRef<Path>>(path: P) -> DispatcherResult<Self> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(DispatcherError::from)
+            .and_then(|manifest_string| Self::from_toml_str(&manifest_string))
+            .map(|manifest| manifest.resolve(path.parent().unwrap_or(Path::new(""))))
+            .map_err(|e| DispatcherError::LoadFailed {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })
+    }
+
+    /// parse the manifest, its paths are kept as written.
+    pub fn from_toml_str(manifest_string: &str) -> DispatcherResult<Self> {
+        let manifest: Self = toml::from_str(manifest_string)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    fn validate(&self) -> DispatcherResult<()> {
+        if self.formats.is_empty() {
+            return Err(DispatcherError::InvalidFieldValue {
+                field: "formats".to_string(),
+                reason: "at least one dump format is needed".to_string(),
+            });
+        }
+        self.archives
+            .iter()
+            .flat_map(|archive| &archive.raw)
+            .try_for_each(RawTableConfig::validate)
+    }
+
+    /// make the relative paths relative to `root` instead of the manifest directory.
+    fn resolve(mut self, root: &Path) -> Self {
+        self.game_dir = root.join(&self.game_dir);
+        self.dump_dir = root.join(&self.dump_dir);
+        self.patched_dir = root.join(&self.patched_dir);
+        for glossary in &mut self.glossaries {
+            *glossary = root.join(&*glossary);
+        }
+        for archive in &mut self.archives {
+            archive.path = self.game_dir.join(&archive.path);
+            for schema in &mut archive.schemas {
+                if !TableSchema::is_builtin(schema) {
+                    *schema = root.join(&*schema).to_string_lossy().into_owned();
+                }
+            }
+        }
+        self
+    }
+
+    pub fn target_encoding(&self) -> TextEncoding {
+        self.target_encoding.unwrap_or(self.source_encoding)
+    }
+
+    /// format of the dumps read back when patching.
+    pub fn patch_format(&self) -> DumpFormat {
+        self.formats.first().copied().unwrap_or_default()
+    }
+}
+
+impl ArchiveManifest {
+    /// the archive lists no table, every known table is looked for.
+    pub fn lists_no_table(&self) -> bool {
+        self.tables.is_empty() && self.schemas.is_empty() && self.raw.is_empty()
+    }
+
+    pub fn load_schemas(&self) -> DispatcherResult<Vec<TableSchema>> {
+        self.schemas
+            .iter()
+            .map(|schema| TableSchema::find(schema))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FieldKind;
+
+    const MANIFEST: &str = r#"
+game_dir = "game"
+dump_dir = "dump"
+patched_dir = "patched"
+source_encoding = "cp932"
+target_encoding = "gbk"
+formats = ["po", "ron"]
+glossaries = ["glossary/characters.csv"]
+
+[[archives]]
+path = "data/script.darc"
+tables = ["string-table", "name-table"]
+
+[[archives]]
+path = "data/menu.darc"
+schemas = ["schemas/menu.ron", "NameTable"]
+raw = [{ magic = "[MENUTX]", count = "u16", count_offset = 2, header_length = 4 }]
+"#;
+
+    fn unknown_field(manifest: &str) -> String {
+        match Manifest::from_toml_str(manifest) {
+            Err(DispatcherError::Toml(e)) => e.message().to_string(),
+            other => panic!("expected a toml error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn manifest_is_parsed_with_its_defaults() {
+        let manifest = Manifest::from_toml_str(MANIFEST).unwrap();
+        assert_eq!(manifest.patch_format(), DumpFormat::Po);
+        assert_eq!(manifest.target_encoding(), TextEncoding::Gbk);
+        assert_eq!(
+            manifest.archives[0].tables,
+            [
+                DataDispatcherType::StringTable,
+                DataDispatcherType::NameTable
+            ]
+        );
+        assert_eq!(
+            manifest.archives[1].raw,
+            [RawTableConfig {
+                magic: "[MENUTX]".to_string(),
+                count: FieldKind::U16,
+                count_offset: 2,
+                header_length: 4,
+            }]
+        );
+        assert!(!manifest.archives[1].lists_no_table());
+
+        let manifest = Manifest::from_toml_str("game_dir = \"game\"\n").unwrap();
+        assert_eq!(
+            (manifest.dump_dir.as_path(), manifest.patched_dir.as_path()),
+            (Path::new("dump"), Path::new("patched"))
+        );
+        assert_eq!(manifest.patch_format(), DumpFormat::Ron);
+        assert_eq!(manifest.target_encoding(), manifest.source_encoding);
+        assert!(manifest.archives.is_empty());
+    }
+
+    #[test]
+    fn paths_are_resolved_against_the_manifest_directory() {
+        let manifest = Manifest::from_toml_str(MANIFEST)
+            .unwrap()
+            .resolve(Path::new("project"));
+        assert_eq!(manifest.game_dir, Path::new("project/game"));
+        assert_eq!(manifest.dump_dir, Path::new("project/dump"));
+        assert_eq!(manifest.patched_dir, Path::new("project/patched"));
+        assert_eq!(
+            manifest.glossaries,
+            [Path::new("project/glossary/characters.csv")]
+        );
+        // archives are under the game directory, built-in schema names are kept.
+        assert_eq!(
+            manifest.archives[0].path,
+            Path::new("project/game/data/script.darc")
+        );
+        assert_eq!(
+            manifest.archives[1].schemas,
+            [
+                Path::new("project/schemas/menu.ron").to_string_lossy(),
+                "NameTable".into()
+            ]
+        );
+
+        let dir = std::env::temp_dir().join(format!("manifest-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(MANIFEST_FILE_NAME), MANIFEST).unwrap();
+        let loaded = Manifest::load(dir.join(MANIFEST_FILE_NAME));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.unwrap().game_dir, dir.join("game"));
+    }
+
+    #[test]
+    fn unknown_and_invalid_fields_are_rejected() {
+        assert!(unknown_field("game_dir = \"game\"\ndump = \"dump\"\n").contains("`dump`"));
+        assert!(
+            unknown_field("game_dir = \"game\"\n[[archives]]\npath = \"a.darc\"\ntable = []\n")
+                .contains("`table`")
+        );
+        assert!(matches!(
+            Manifest::from_toml_str("dump_dir = \"dump\"\n"),
+            Err(DispatcherError::Toml(_))
+        ));
+        assert!(matches!(
+            Manifest::from_toml_str("game_dir = \"game\"\nformats = []\n"),
+            Err(DispatcherError::InvalidFieldValue { field, .. }) if field == "formats"
+        ));
+        assert!(matches!(
+            Manifest::from_toml_str(
+                "game_dir = \"game\"\n[[archives]]\npath = \"a.darc\"\n\
+                 raw = [{ magic = \"[MENUTX]\", count = \"u32\", count_offset = 2, \
+                 header_length = 4 }]\n"
+            ),
+            Err(DispatcherError::InvalidSchema { .. })
+        ));
+        assert!(matches!(
+            Manifest::load("missing-manifest.toml"),
+            Err(DispatcherError::LoadFailed { .. })
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{BinaryPatch, DataDispatcherHeader, text::DecodeIssue};
+use serde::{Deserialize, Serialize};
+
+/// item in name table patch:
+///
+/// ```{text}
+/// 0                   1
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |LEN|            DATA           |
+/// +-+-+                     +-+-+-+
+/// |                         | PAD |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 0-1: length, u16 little-endian;
+/// 2-: data, string (length bytes, padding to 2 bytes alignment);
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, BinaryPatch)]
+pub struct NameTableItem {
+    #[skip]
+    length: u16,
+    #[len_prefix(u16, store = length)]
+    #[encoding]
+    data: String,
+}
+
+impl NameTableItem {
+    /// length of the data as read from the binary file, recomputed when serializing.
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    pub fn set_data(&mut self, data: impl Into<String>) {
+        self.data = data.into();
+    }
+}
+
+/// structure of name table patch:
+///
+/// ```{text}
+/// 0                   1
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |[|M|E|S|N|A|M|]|UNK|CNT|LEN|   |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |    DATA   |LEN|      DATA     |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 0-7: header, `[MESNAM]`;
+/// 8-9: unknown (assume as padding), u16 little-endian;
+/// 10-11: item_count, u16 little-endian;
+/// 12-: item, [NameTableItem];
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, BinaryPatch)]
+#[magic(b"[MESNAM]")]
+pub struct NameTable {
+    #[le]
+    assume_padding: u16,
+    #[le]
+    #[count_of(items)]
+    item_count: u16,
+    #[items]
+    items: Vec<NameTableItem>,
+}
+
+impl NameTable {
+    pub fn assume_padding(&self) -> u16 {
+        self.assume_padding
+    }
+
+    /// item count as read from the binary file, recomputed when serializing.
+    pub fn item_count(&self) -> u16 {
+        self.item_count
+    }
+
+    pub fn items(&self) -> &[NameTableItem] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut Vec<NameTableItem> {
+        &mut self.items
+    }
+
+    /// items whose data could not be fully decoded, `base_offset` is the offset of the magic header.
+    pub fn decode_issues(&self, base_offset: u64) -> Vec<DecodeIssue> {
+        let mut offset = base_offset + 12;
+        self.items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let issue = DecodeIssue::check(Self::TABLE_NAME, index, None, offset, &item.data);
+                offset += 2 + item.length as u64;
+                issue
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeserializePatch, SerializePatch, TextEncoding};
+
+    fn item(data: &[u8]) -> Vec<u8> {
+        let mut bytes = (data.len() as u16).to_le_bytes().to_vec();
+        bytes.extend(data);
+        bytes
+    }
+
+    /// "結希" and "Yuki".
+    fn table() -> Vec<u8> {
+        let mut bytes = b"[MESNAM]".to_vec();
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend(2u16.to_le_bytes());
+        bytes.extend(item(b"\x8C\x8B\x8A\xF3"));
+        bytes.extend(item(b"Yuki"));
+        bytes
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let bytes = table();
+        let table = NameTable::from_bytes(&bytes, TextEncoding::Cp932).unwrap();
+        assert_eq!((table.assume_padding(), table.item_count()), (0, 2));
+        let data: Vec<_> = table.items().iter().map(NameTableItem::data).collect();
+        assert_eq!(data, ["結希", "Yuki"]);
+        assert_eq!(table.items()[0].length(), 4);
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), bytes);
+    }
+
+    #[test]
+    fn edited_items_are_counted_again() {
+        let mut table = NameTable::from_bytes(&table(), TextEncoding::Cp932).unwrap();
+        table.items_mut()[1].set_data("Yuki-chan");
+        table.items_mut().push(NameTableItem::default());
+
+        let mut expected = b"[MESNAM]".to_vec();
+        expected.extend(0u16.to_le_bytes());
+        expected.extend(3u16.to_le_bytes());
+        expected.extend(item(b"\x8C\x8B\x8A\xF3"));
+        expected.extend(item(b"Yuki-chan"));
+        expected.extend(item(b""));
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), expected);
+    }
+
+    #[test]
+    fn unmappable_text_is_an_error() {
+        let mut table = NameTable::from_bytes(&table(), TextEncoding::Cp932).unwrap();
+        table.items_mut()[0].set_data("結希😀");
+        assert!(table.to_bytes(TextEncoding::Cp932).is_err());
+    }
+
+    #[test]
+    fn truncated_table_is_an_error() {
+        let bytes = table();
+        assert!(NameTable::from_bytes(&bytes[..bytes.len() - 1], TextEncoding::Cp932).is_err());
+    }
+}
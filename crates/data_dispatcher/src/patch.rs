@@ -0,0 +1,307 @@
+use crate::{
+    DarcArchive, DataDispatcher, DataDispatcherType, DispatcherError, DispatcherResult, DumpFormat,
+    ImportSummary, LoadDump, PoFile, SerializePatch, Sheet, SheetFormat, TranslationProject,
+    XliffFile, text::TextEncoding,
+};
+use std::{
+    io::{Cursor, Seek, SeekFrom},
+    path::Path,
+};
+use utils::WriteBinary;
+
+impl DataDispatcher {
+    /// the table of the file at `path` written as `format`, to be patched over the
+    /// `index`-th table of its type in `container`.
+    ///
+    /// ron, json and yaml dumps hold the whole table. the translations of the other formats
+    /// are imported into the table read from `container`, the summary tells the keys
+    /// matching no item.
+    pub fn load_patch(
+        path: &Path,
+        format: DumpFormat,
+        container: &[u8],
+        encoding: TextEncoding,
+        index: usize,
+    ) -> DispatcherResult<(DataDispatcher, ImportSummary)> {
+        let original = |table: DataDispatcherType| {
+            DataDispatcher::from(table).read_nth(container, encoding, index)
+        };
+        let sheet = |format| -> DispatcherResult<_> {
+            let sheet = Sheet::load(path, format)?;
+            let mut data = original(DataDispatcherType::StringTable)?;
+            let summary = sheet.apply(&mut data)?;
+            Ok((data, summary))
+        };
+        match format {
+            // told apart by the extension like `format`.
+            DumpFormat::Ron | DumpFormat::Json | DumpFormat::Yaml => {
+                Ok((DataDispatcher::load_dump(path)?, ImportSummary::default()))
+            }
+            DumpFormat::Po => {
+                let po = PoFile::load(path)?;
+                let mut data = original(po.table)?;
+                let summary = po.apply(&mut data)?;
+                Ok((data, summary))
+            }
+            // both versions are read, told apart by the root element.
+            DumpFormat::Xliff | DumpFormat::Xliff2 => {
+                let xliff = XliffFile::load(path)?;
+                let mut data = original(xliff.table)?;
+                let summary = xliff.apply(&mut data)?;
+                Ok((data, summary))
+            }
+            DumpFormat::Project => {
+                let project = TranslationProject::load(path)?;
+                let mut data = original(project.table)?;
+                let summary = project.apply(&mut data)?;
+                Ok((data, summary))
+            }
+            DumpFormat::Csv => sheet(SheetFormat::Csv),
+            DumpFormat::Tsv => sheet(SheetFormat::Tsv),
+            DumpFormat::Xlsx => sheet(SheetFormat::Xlsx),
+        }
+    }
+
+    /// write the patch back into `container`, a darc archive or a file holding the table.
+    pub fn patch(&self, container: &[u8], encoding: TextEncoding) -> DispatcherResult<Vec<u8>> {
+        self.patch_nth(container, encoding, 0)
+    }
+
+    /// write the patch back over the `index`-th table of the same type in `container`,
+    /// counted across the entries of a darc archive like the numbered dumps.
+    pub fn patch_nth(
+        &self,
+        container: &[u8],
+        encoding: TextEncoding,
+        index: usize,
+    ) -> DispatcherResult<Vec<u8>> {
+        match DarcArchive::is_darc(container) {
+            true => self.patch_darc(container, encoding, index),
+            false => self.patch_container(container, encoding, index),
+        }
+    }
+
+    fn header_not_found(&self) -> DispatcherError {
+        DispatcherError::HeaderNotFound {
+            header: String::from_utf8_lossy(self.magic_header()).into_owned(),
+        }
+    }
+
+    /// `(start, end)` of the first `limit` tables of the same type in `buffer`, magic
+    /// headers inside a table are skipped.
+    fn find_same(
+        &self,
+        buffer: &[u8],
+        encoding: TextEncoding,
+        limit: usize,
+    ) -> DispatcherResult<Vec<(usize, usize)>> {
+        let header = self.magic_header();
+        let mut cursor = Cursor::new(buffer);
+        let mut tables: Vec<(usize, usize)> = vec![];
+        for (start_pos, window) in buffer.windows(header.len()).enumerate() {
+            if tables.len() == limit {
+                break;
+            }
+            if window != header || tables.last().is_some_and(|&(_, end)| start_pos < end) {
+                continue;
+            }
+            // read the original patch to find out where it ends.
+            cursor.seek(SeekFrom::Start(start_pos as u64))?;
+            self.deserialize_same(&mut cursor, encoding)?;
+            tables.push((start_pos, cursor.position() as usize));
+        }
+        Ok(tables)
+    }
+
+    /// rebuild the patch and splice it into the container over the `index`-th table.
+    fn patch_container(
+        &self,
+        container: &[u8],
+        encoding: TextEncoding,
+        index: usize,
+    ) -> DispatcherResult<Vec<u8>> {
+        let tables = self.find_same(container, encoding, index + 1)?;
+        let &(start_pos, end_pos) = tables.get(index).ok_or_else(|| self.header_not_found())?;
+
+        let mut rebuilt = Cursor::new(Vec::with_capacity(container.len()));
+        rebuilt.write_bytes("container head", &container[..start_pos])?;
+        self.serialize_patch(&mut rebuilt, encoding)?;
+        rebuilt.write_bytes("container tail", &container[end_pos..])?;
+
+        Ok(rebuilt.into_inner())
+    }
+
+    /// path of the darc entry holding the `index`-th table, and the index of the table
+    /// among the tables of the same type in the entry.
+    fn find_entry(
+        &self,
+        archive: &DarcArchive,
+        encoding: TextEncoding,
+        mut index: usize,
+    ) -> DispatcherResult<(String, usize)> {
+        for entry in archive.entries()? {
+            let entry_data = archive.entry_data(&entry.path).unwrap_or_default();
+            let found = self.find_same(entry_data, encoding, index + 1)?.len();
+            if found > index {
+                return Ok((entry.path, index));
+            }
+            index -= found;
+        }
+        Err(self.header_not_found())
+    }
+
+    /// patch the darc entry holding the `index`-th table, then repack the archive with new
+    /// offsets.
+    fn patch_darc(
+        &self,
+        container: &[u8],
+        encoding: TextEncoding,
+        index: usize,
+    ) -> DispatcherResult<Vec<u8>> {
+        let mut archive = DarcArchive::parse(container)?;
+        let (entry_path, index) = self.find_entry(&archive, encoding, index)?;
+        let entry_data = archive.entry_data(&entry_path).unwrap_or_default();
+        let patched = self.patch_container(entry_data, encoding, index)?;
+        archive.set_entry_data(&entry_path, patched)?;
+        archive.to_bytes()
+    }
+
+    /// read the `index`-th table of the same type in `container`, counted like
+    /// [Self::patch_nth].
+    pub fn read_nth(
+        &self,
+        container: &[u8],
+        encoding: TextEncoding,
+        index: usize,
+    ) -> DispatcherResult<DataDispatcher> {
+        if !DarcArchive::is_darc(container) {
+            return self.read_in_container(container, encoding, index);
+        }
+        let archive = DarcArchive::parse(container)?;
+        let (entry_path, index) = self.find_entry(&archive, encoding, index)?;
+        let entry_data = archive.entry_data(&entry_path).unwrap_or_default();
+        self.read_in_container(entry_data, encoding, index)
+    }
+
+    fn read_in_container(
+        &self,
+        container: &[u8],
+        encoding: TextEncoding,
+        index: usize,
+    ) -> DispatcherResult<DataDispatcher> {
+        let tables = self.find_same(container, encoding, index + 1)?;
+        let &(start_pos, _) = tables.get(index).ok_or_else(|| self.header_not_found())?;
+        let mut cursor = Cursor::new(container);
+        cursor.seek(SeekFrom::Start(start_pos as u64))?;
+        self.deserialize_same(&mut cursor, encoding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeserializePatch, NameTable};
+
+    fn name_table(names: &[&str]) -> Vec<u8> {
+        let mut bytes = b"[MESNAM]".to_vec();
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend((names.len() as u16).to_le_bytes());
+        for name in names {
+            bytes.extend((name.len() as u16).to_le_bytes());
+            bytes.extend(name.as_bytes());
+        }
+        bytes
+    }
+
+    fn renamed(table: &[u8], name: &str) -> DataDispatcher {
+        let mut table = NameTable::from_bytes(table, TextEncoding::Cp932).unwrap();
+        table.items_mut()[0].set_data(name);
+        DataDispatcher::NameTable(table)
+    }
+
+    #[test]
+    fn patch_splices_the_table_into_the_container() {
+        let head = b"head\0\x01\x02".to_vec();
+        let tail = b"\x03\x04tail".to_vec();
+        let container = [head.clone(), name_table(&["Yuki", "Aoi"]), tail.clone()].concat();
+
+        let data = renamed(&container[head.len()..], "Yuki-chan");
+        let patched = data.patch(&container, TextEncoding::Cp932).unwrap();
+        assert_eq!(
+            patched,
+            [head, name_table(&["Yuki-chan", "Aoi"]), tail].concat()
+        );
+
+        assert!(matches!(
+            data.patch(b"no table here", TextEncoding::Cp932),
+            Err(DispatcherError::HeaderNotFound { header }) if header == "[MESNAM]"
+        ));
+    }
+
+    #[test]
+    fn patch_nth_replaces_only_that_table() {
+        let first = name_table(&["Yuki", "Aoi"]);
+        let second = name_table(&["Mio"]);
+        let container = [&b"head"[..], &first, b"\0\0", &second, b"tail"].concat();
+        let second_start = 4 + first.len() + 2;
+
+        let data = renamed(&container[second_start..], "Mio-san");
+        let patched = data.patch_nth(&container, TextEncoding::Cp932, 1).unwrap();
+        assert_eq!(&patched[..second_start], &container[..second_start]);
+        assert_eq!(
+            &patched[second_start..],
+            [name_table(&["Mio-san"]), b"tail".to_vec()].concat()
+        );
+        assert_eq!(
+            DataDispatcher::from(DataDispatcherType::NameTable)
+                .read_nth(&patched, TextEncoding::Cp932, 1)
+                .unwrap()
+                .to_bytes(TextEncoding::Cp932)
+                .unwrap(),
+            name_table(&["Mio-san"])
+        );
+
+        assert!(matches!(
+            data.patch_nth(&container, TextEncoding::Cp932, 2),
+            Err(DispatcherError::HeaderNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn darc_entries_are_patched_in_place() {
+        let dir = std::env::temp_dir().join(format!("patch-darc-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        std::fs::write(dir.join("data/a.bin"), name_table(&["Yuki", "Aoi"])).unwrap();
+        std::fs::write(
+            dir.join("data/b.bin"),
+            [b"b\0", &name_table(&["Mio"])[..]].concat(),
+        )
+        .unwrap();
+        std::fs::write(dir.join("other.bin"), b"other").unwrap();
+        let archive = DarcArchive::from_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let container = archive.unwrap().to_bytes().unwrap();
+
+        // the second table of the archive is the first one of `data/b.bin`.
+        let data = renamed(&name_table(&["Mio"]), "Mio-san");
+        let patched = data.patch_nth(&container, TextEncoding::Cp932, 1).unwrap();
+        let original = DarcArchive::parse(&container).unwrap();
+        let archive = DarcArchive::parse(&patched).unwrap();
+        assert_eq!(archive.entries().unwrap().len(), 3);
+        for path in ["data/a.bin", "other.bin"] {
+            assert_eq!(archive.entry_data(path), original.entry_data(path));
+        }
+        assert_eq!(
+            archive.entry_data("data/b.bin").unwrap(),
+            [b"b\0", &name_table(&["Mio-san"])[..]].concat()
+        );
+        assert_eq!(
+            DataDispatcher::from(DataDispatcherType::NameTable)
+                .read_nth(&patched, TextEncoding::Cp932, 1)
+                .unwrap()
+                .to_bytes(TextEncoding::Cp932)
+                .unwrap(),
+            name_table(&["Mio-san"])
+        );
+    }
+}
@@ -0,0 +1,553 @@
+use crate::{DataDispatcher, DataDispatcherType, DispatcherError, DispatcherResult, ImportSummary};
+use std::{fmt, fs, path::Path};
+
+const FORMAT: &str = "po";
+
+/// header field naming the table type of the po file, its gettext domain.
+const TABLE_HEADER: &str = "X-Imojiru-Table";
+
+/// entry of a [PoFile], one per table item.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoEntry {
+    /// `id` of string table items, the item index otherwise.
+    pub context: String,
+    /// original text, without the terminator and the padding.
+    pub source: String,
+    /// empty while untranslated.
+    pub translation: String,
+    /// marked `#, fuzzy`, not imported until reviewed.
+    pub fuzzy: bool,
+    /// notes for the translators, written as `#.` extracted comments.
+    pub notes: Vec<String>,
+}
+
+/// gettext po file of the texts of a known table, a pot template when exported.
+///
+/// ```{text}
+/// msgid ""
+/// msgstr ""
+/// "Content-Type: text/plain; charset=UTF-8\n"
+/// "Content-Transfer-Encoding: 8bit\n"
+/// "X-Imojiru-Table: StringTable\n"
+///
+/// msgctxt "1024"
+/// msgid "text of the item with id 1024"
+/// msgstr ""
+/// ```
+///
+/// each table type is its own domain, named in the `X-Imojiru-Table` header. translations
+/// are imported into the table read from the original file, which keeps the terminators
+/// and the header fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoFile {
+    pub table: DataDispatcherType,
+    pub entries: Vec<PoEntry>,
+}
+
+impl PoFile {
+    /// po template of the texts of the table.
+    pub fn export(data: &DataDispatcher) -> DispatcherResult<Self> {
+        let unsupported = || DispatcherError::UnsupportedTable {
+            table: data.type_name().to_string(),
+            format: FORMAT,
+        };
+        let table = data.patch_type().ok_or_else(unsupported)?;
+        let entries = data
+            .text_entries(FORMAT)?
+            .into_iter()
+            .map(|entry| PoEntry {
+                context: entry.key,
+                source: entry.text,
+                ..PoEntry::default()
+            })
+            .collect();
+        Ok(Self { table, entries })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> DispatcherResult<Self> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(DispatcherError::from)
+            .and_then(|po_string| Self::from_po_str(&po_string))
+            .map_err(|e| DispatcherError::LoadFailed {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })
+    }
+
+    pub fn from_po_str(po_string: &str) -> DispatcherResult<Self> {
+        let mut parser = PoParser::default();
+        for (index, line) in po_string.lines().enumerate() {
+            parser.line = index + 1;
+            parser.parse_line(line.trim())?;
+        }
+        parser.finish_entry()?;
+
+        let table = parser
+            .table
+            .ok_or_else(|| DispatcherError::InvalidTranslation {
+                format: FORMAT,
+                line: 1,
+                reason: format!("no `{TABLE_HEADER}` header naming the table type"),
+            })?;
+        Ok(Self {
+            table,
+            entries: parser.entries,
+        })
+    }
+
+    /// write the translated entries into `data`, the table read from the original file.
+    pub fn apply(&self, data: &mut DataDispatcher) -> DispatcherResult<ImportSummary> {
+        let texts = self.entries.iter().map(|entry| {
+            let ready = !entry.fuzzy && !entry.translation.is_empty();
+            (
+                entry.context.clone(),
+                ready.then(|| entry.translation.clone()),
+            )
+        });
+        data.import_texts(self.table, texts, FORMAT)
+    }
+}
+
+impl fmt::Display for PoFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "msgid \"\"")?;
+        writeln!(f, "msgstr \"\"")?;
+        writeln!(f, "\"Content-Type: text/plain; charset=UTF-8\\n\"")?;
+        writeln!(f, "\"Content-Transfer-Encoding: 8bit\\n\"")?;
+        writeln!(f, "\"{TABLE_HEADER}: {}\\n\"", self.table.table_name())?;
+        for entry in &self.entries {
+            writeln!(f)?;
+            for line in entry.notes.iter().flat_map(|note| note.lines()) {
+                writeln!(f, "#. {line}")?;
+            }
+            if entry.fuzzy {
+                writeln!(f, "#, fuzzy")?;
+            }
+            write_field(f, "msgctxt", &entry.context)?;
+            write_field(f, "msgid", &entry.source)?;
+            write_field(f, "msgstr", &entry.translation)?;
+        }
+        Ok(())
+    }
+}
+
+/// `keyword "text"`, split after each line break when the text spans several lines.
+fn write_field(f: &mut fmt::Formatter<'_>, keyword: &str, text: &str) -> fmt::Result {
+    if !text.trim_end_matches('\n').contains('\n') {
+        return writeln!(f, "{keyword} \"{}\"", escape(text));
+    }
+    writeln!(f, "{keyword} \"\"")?;
+    for line in text.split_inclusive('\n') {
+        writeln!(f, "\"{}\"", escape(line))?;
+    }
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            // nulls and other control characters as octal escapes.
+            ch if (ch as u32) < 0x20 || ch == '\x7F' => {
+                escaped.push_str(&format!("\\{:03o}", ch as u32))
+            }
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// field of a po entry the string lines are appended to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Context,
+    Source,
+    Translation,
+}
+
+#[derive(Debug, Default)]
+struct PoParser {
+    line: usize,
+    table: Option<DataDispatcherType>,
+    entries: Vec<PoEntry>,
+    entry: PoEntry,
+    field: Option<Field>,
+    /// fields of the current entry, to tell a new entry from the current one.
+    has_source: bool,
+    has_translation: bool,
+}
+
+impl PoParser {
+    fn error(&self, reason: impl Into<String>) -> DispatcherError {
+        DispatcherError::InvalidTranslation {
+            format: FORMAT,
+            line: self.line,
+            reason: reason.into(),
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> DispatcherResult<()> {
+        if line.is_empty() {
+            return Ok(());
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            if self.has_translation {
+                self.finish_entry()?;
+            }
+            // other comments, `#~` obsolete entries included, are dropped.
+            if let Some(flags) = comment.strip_prefix(',') {
+                self.entry.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+            } else if let Some(note) = comment.strip_prefix('.') {
+                self.entry.notes.push(note.trim().to_string());
+            }
+            return Ok(());
+        }
+        if line.starts_with('"') {
+            let text = self.unquote(line)?;
+            match self.field {
+                Some(Field::Context) => self.entry.context.push_str(&text),
+                Some(Field::Source) => self.entry.source.push_str(&text),
+                Some(Field::Translation) => self.entry.translation.push_str(&text),
+                None => return Err(self.error("string outside of an entry")),
+            }
+            return Ok(());
+        }
+
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let text = self.unquote(rest.trim())?;
+        if matches!(keyword, "msgctxt" | "msgid") && self.has_translation {
+            self.finish_entry()?;
+        }
+        if keyword == "msgctxt" && self.has_source {
+            return Err(self.error("`msgctxt` after `msgid`"));
+        }
+        match keyword {
+            "msgctxt" => {
+                self.entry.context = text;
+                self.field = Some(Field::Context);
+            }
+            "msgid" => {
+                self.entry.source = text;
+                self.has_source = true;
+                self.field = Some(Field::Source);
+            }
+            "msgstr" if self.has_source => {
+                self.entry.translation = text;
+                self.has_translation = true;
+                self.field = Some(Field::Translation);
+            }
+            "msgstr" => return Err(self.error("`msgstr` without `msgid`")),
+            "msgid_plural" | "msgstr[0]" => {
+                return Err(self.error("plural forms are not supported"));
+            }
+            keyword => return Err(self.error(format!("unknown keyword `{keyword}`"))),
+        }
+        Ok(())
+    }
+
+    fn finish_entry(&mut self) -> DispatcherResult<()> {
+        let entry = std::mem::take(&mut self.entry);
+        let complete = self.has_source && self.has_translation;
+        self.field = None;
+        self.has_source = false;
+        self.has_translation = false;
+
+        // notes alone make no entry.
+        let notes_only = PoEntry {
+            notes: vec![],
+            ..entry.clone()
+        } == PoEntry::default();
+        if !complete && notes_only {
+            return Ok(());
+        }
+        if !complete {
+            return Err(self.error("entry without `msgid` or `msgstr`"));
+        }
+        // the header is the entry without context and source.
+        if entry.context.is_empty() && entry.source.is_empty() {
+            return self.parse_header(&entry.translation);
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    fn parse_header(&mut self, header: &str) -> DispatcherResult<()> {
+        let table_name = header.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            (name.trim() == TABLE_HEADER).then(|| value.trim())
+        });
+        if let Some(table_name) = table_name {
+            let table = DataDispatcherType::from_table_name(table_name)
+                .ok_or_else(|| self.error(format!("unknown table type `{table_name}`")))?;
+            self.table = Some(table);
+        }
+        Ok(())
+    }
+
+    /// the text of a quoted po string, with its escapes resolved.
+    fn unquote(&self, quoted: &str) -> DispatcherResult<String> {
+        let inner = quoted
+            .strip_prefix('"')
+            .and_then(|quoted| quoted.strip_suffix('"'))
+            .filter(|_| quoted.len() >= 2)
+            .ok_or_else(|| self.error(format!("expected a quoted string, found `{quoted}`")))?;
+
+        let mut text = String::with_capacity(inner.len());
+        let mut chars = inner.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                text.push(ch);
+                continue;
+            }
+            let escaped = chars
+                .next()
+                .ok_or_else(|| self.error("string ends with a lone `\\`"))?;
+            match escaped {
+                'n' => text.push('\n'),
+                'r' => text.push('\r'),
+                't' => text.push('\t'),
+                'a' => text.push('\x07'),
+                'b' => text.push('\x08'),
+                'f' => text.push('\x0C'),
+                'v' => text.push('\x0B'),
+                '\\' | '"' | '\'' | '?' => text.push(escaped),
+                '0'..='7' => {
+                    let mut code = escaped.to_digit(8).unwrap_or_default();
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|digit| digit.to_digit(8)) {
+                            Some(digit) => {
+                                code = code * 8 + digit;
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    text.push(char::from_u32(code).unwrap_or_default());
+                }
+                'x' => {
+                    let mut digits = String::new();
+                    while let Some(&digit) = chars.peek().filter(|digit| digit.is_ascii_hexdigit())
+                    {
+                        digits.push(digit);
+                        chars.next();
+                    }
+                    let ch = u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| self.error(format!("invalid escape `\\x{digits}`")))?;
+                    text.push(ch);
+                }
+                escaped => return Err(self.error(format!("unknown escape `\\{escaped}`"))),
+            }
+        }
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeserializePatch, NameTable, TextEncoding};
+
+    const PO: &str = r#"# translator comment
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+"X-Imojiru-Table: NameTable\n"
+
+msgctxt "0"
+msgid "結希"
+msgstr "Yuki"
+
+#, fuzzy, c-format
+msgctxt "1"
+msgid ""
+"first line\n"
+"second \"line\"\t\101\0"
+msgstr "guess"
+
+msgctxt "2"
+msgid "お兄さん"
+msgstr ""
+
+#~ msgctxt "3"
+#~ msgid "obsolete"
+#~ msgstr "dropped"
+"#;
+
+    fn name_table_bytes(names: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = b"[MESNAM]".to_vec();
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend((names.len() as u16).to_le_bytes());
+        for name in names {
+            bytes.extend((name.len() as u16).to_le_bytes());
+            bytes.extend(*name);
+        }
+        bytes
+    }
+
+    /// name table of `names` in windows-932.
+    fn name_table(names: &[&str]) -> DataDispatcher {
+        let names: Vec<_> = names
+            .iter()
+            .map(|name| TextEncoding::Cp932.encode(name).unwrap())
+            .collect();
+        let names: Vec<_> = names.iter().map(Vec::as_slice).collect();
+        let bytes = name_table_bytes(&names);
+        DataDispatcher::NameTable(NameTable::from_bytes(&bytes, TextEncoding::Cp932).unwrap())
+    }
+
+    fn parse_error(po_string: &str) -> String {
+        match PoFile::from_po_str(po_string) {
+            Err(error) => error.to_string(),
+            Ok(po) => panic!("expected an error, got {po:?}"),
+        }
+    }
+
+    #[test]
+    fn entries_are_parsed() {
+        let po = PoFile::from_po_str(PO).unwrap();
+        assert_eq!(po.table, DataDispatcherType::NameTable);
+        assert_eq!(
+            po.entries,
+            [
+                PoEntry {
+                    context: "0".to_string(),
+                    source: "結希".to_string(),
+                    translation: "Yuki".to_string(),
+                    ..PoEntry::default()
+                },
+                PoEntry {
+                    context: "1".to_string(),
+                    source: "first line\nsecond \"line\"\tA\0".to_string(),
+                    translation: "guess".to_string(),
+                    fuzzy: true,
+                    ..PoEntry::default()
+                },
+                PoEntry {
+                    context: "2".to_string(),
+                    source: "お兄さん".to_string(),
+                    ..PoEntry::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn written_file_is_parsed_back() {
+        let po = PoFile::from_po_str(PO).unwrap();
+        let po_string = po.to_string();
+        assert!(po_string.contains("msgid \"\"\n\"first line\\n\"\n\"second"));
+        assert!(po_string.contains("\\tA\\000\"\n"));
+        assert_eq!(PoFile::from_po_str(&po_string).unwrap(), po);
+    }
+
+    #[test]
+    fn only_finished_translations_are_applied() {
+        let mut data = name_table(&["結希", "first line", "お兄さん"]);
+        let summary = PoFile::from_po_str(PO).unwrap().apply(&mut data).unwrap();
+        assert_eq!((summary.translated, summary.untranslated), (1, 2));
+        let texts: Vec<_> = data
+            .text_entries(FORMAT)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.text)
+            .collect();
+        assert_eq!(texts, ["Yuki", "first line", "お兄さん"]);
+
+        let mut data = name_table(&[]);
+        let summary = PoFile::from_po_str(PO).unwrap().apply(&mut data).unwrap();
+        assert_eq!(
+            (summary.translated, summary.unknown_keys),
+            (0, vec!["0".to_string()])
+        );
+    }
+
+    #[test]
+    fn untouched_duplicate_characters_survive_a_round_trip() {
+        let container = name_table_bytes(&[b"\xED\x40\x87\x90", b"Yuki"]);
+        let mut data = DataDispatcher::NameTable(
+            NameTable::from_bytes(&container, TextEncoding::Cp932).unwrap(),
+        );
+        let mut po = PoFile::export(&data).unwrap();
+        po.entries[0].translation = po.entries[0].source.clone();
+        po.entries[1].translation = "Yuuki".to_string();
+        let po = PoFile::from_po_str(&po.to_string()).unwrap();
+        po.apply(&mut data).unwrap();
+
+        assert_eq!(
+            data.patch(&container, TextEncoding::Cp932).unwrap(),
+            name_table_bytes(&[b"\xED\x40\x87\x90", b"Yuuki"])
+        );
+    }
+
+    #[test]
+    fn invalid_files_report_the_line() {
+        let header = "msgid \"\"\nmsgstr \"X-Imojiru-Table: NameTable\\n\"\n";
+        let cases = [
+            ("msgctxt \"0\"\nmsgstr \"b\"", 4, "`msgstr` without `msgid`"),
+            (
+                "msgid \"a\"\nmsgid_plural \"as\"",
+                4,
+                "plural forms are not supported",
+            ),
+            ("msgid \"a\"\nmsgctxt \"0\"", 4, "`msgctxt` after `msgid`"),
+            ("msgid \"a\"\nmsgstr \"b\\q\"", 4, "unknown escape `\\q`"),
+            ("msgid \"a\"\nmsgfoo \"b\"", 4, "unknown keyword `msgfoo`"),
+            ("#, fuzzy\n\"orphan\"", 4, "string outside of an entry"),
+            ("msgid \"a\"", 3, "entry without `msgid` or `msgstr`"),
+        ];
+        for (body, line, reason) in cases {
+            let error = parse_error(&format!("{header}{body}\n"));
+            assert_eq!(error, format!("invalid po file at line {line}: {reason}"));
+        }
+
+        let error = parse_error("msgid \"a\"\nmsgstr \"b\"\n");
+        assert!(error.contains("no `X-Imojiru-Table` header"), "{error}");
+        let error = parse_error("msgid \"\"\nmsgstr \"X-Imojiru-Table: Unknown\\n\"\n");
+        assert!(error.contains("unknown table type `Unknown`"), "{error}");
+    }
+
+    #[test]
+    fn notes_are_written_as_extracted_comments_and_read_back() {
+        let po = PoFile {
+            table: DataDispatcherType::StringTable,
+            entries: vec![PoEntry {
+                context: "1024".to_string(),
+                source: "結希".to_string(),
+                translation: String::new(),
+                fuzzy: true,
+                notes: vec!["glossary: 結希 = Yuki".to_string()],
+            }],
+        };
+        let po_string = po.to_string();
+        assert!(po_string.contains("\n#. glossary: 結希 = Yuki\n#, fuzzy\nmsgctxt \"1024\"\n"));
+        assert_eq!(PoFile::from_po_str(&po_string).unwrap(), po);
+    }
+
+    #[test]
+    fn hex_escapes_out_of_range_are_an_error() {
+        let parser = PoParser::default();
+        assert_eq!(parser.unquote(r#""\x41\x7e5e""#).unwrap(), "A\u{7E5E}");
+        for quoted in [
+            r#""\x110000""#,
+            r#""\xfffffffff""#,
+            r#""\xd800""#,
+            r#""\x""#,
+        ] {
+            assert!(
+                parser
+                    .unquote(quoted)
+                    .unwrap_err()
+                    .to_string()
+                    .contains("invalid escape")
+            );
+        }
+    }
+}
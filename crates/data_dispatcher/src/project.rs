@@ -0,0 +1,212 @@
+use crate::{
+    DataDispatcher, DataDispatcherType, DispatcherError, DispatcherResult, ImportSummary,
+    TranslationStatus,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+const FORMAT: &str = "translation project";
+
+/// entry of a [TranslationProject], one per table item.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectEntry {
+    /// `id` of string table items, the item index otherwise.
+    pub key: String,
+    /// original text, without the terminator and the padding.
+    pub original: String,
+    #[serde(default)]
+    pub translation: String,
+    #[serde(default)]
+    pub status: TranslationStatus,
+    /// translator or reviewer of the entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// free-form comments of the translators.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+}
+
+impl ProjectEntry {
+    /// translated or reviewed, drafts are left out.
+    pub fn is_ready(&self) -> bool {
+        self.status.is_ready() && !self.translation.is_empty()
+    }
+}
+
+/// bilingual translation project of the texts of a known table, kept as json.
+///
+/// ```{json}
+/// {
+///   "table": "string-table",
+///   "entries": [
+///     {
+///       "key": "1024",
+///       "original": "text of the item with id 1024",
+///       "translation": "translated text",
+///       "status": "reviewed",
+///       "author": "translator",
+///       "notes": "comment of the translator"
+///     }
+///   ]
+/// }
+/// ```
+///
+/// unlike a dump, the project keeps the original next to the translation, with the status
+/// of the review. only translated and reviewed entries are built into the table read from
+/// the original file, the other items keep their bytes from the original file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranslationProject {
+    pub table: DataDispatcherType,
+    pub entries: Vec<ProjectEntry>,
+}
+
+impl TranslationProject {
+    /// project of the texts of the table, every entry untranslated.
+    pub fn export(data: &DataDispatcher) -> DispatcherResult<Self> {
+        let unsupported = || DispatcherError::UnsupportedTable {
+            table: data.type_name().to_string(),
+            format: FORMAT,
+        };
+        let table = data.patch_type().ok_or_else(unsupported)?;
+        let entries = data
+            .text_entries(FORMAT)?
+            .into_iter()
+            .map(|entry| ProjectEntry {
+                key: entry.key,
+                original: entry.text,
+                ..ProjectEntry::default()
+            })
+            .collect();
+        Ok(Self { table, entries })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> DispatcherResult<Self> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(DispatcherError::from)
+            .and_then(|project_string| Self::from_json_str(&project_string))
+            .map_err(|e| DispatcherError::LoadFailed {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })
+    }
+
+    pub fn from_json_str(project_string: &str) -> DispatcherResult<Self> {
+        Ok(serde_json::from_str(project_string)?)
+    }
+
+    pub fn to_json_string(&self) -> DispatcherResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// write the ready translations into `data`, the table read from the original file.
+    pub fn apply(&self, data: &mut DataDispatcher) -> DispatcherResult<ImportSummary> {
+        let texts = self.entries.iter().map(|entry| {
+            (
+                entry.key.clone(),
+                entry.is_ready().then(|| entry.translation.clone()),
+            )
+        });
+        data.import_texts(self.table, texts, FORMAT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeserializePatch, NameTable, TextEncoding};
+
+    const PROJECT: &str = r#"{
+  "table": "name-table",
+  "entries": [
+    {
+      "key": "0",
+      "original": "お兄さん",
+      "translation": "哥哥",
+      "status": "draft",
+      "author": "translator"
+    },
+    {
+      "key": "1",
+      "original": "結希",
+      "translation": "结希",
+      "status": "reviewed",
+      "notes": "the little sister"
+    },
+    {
+      "key": "9",
+      "original": "",
+      "translation": "unknown",
+      "status": "translated"
+    }
+  ]
+}"#;
+
+    fn name_table_bytes(names: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = b"[MESNAM]".to_vec();
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend((names.len() as u16).to_le_bytes());
+        for name in names {
+            bytes.extend((name.len() as u16).to_le_bytes());
+            bytes.extend(name);
+        }
+        bytes
+    }
+
+    #[test]
+    fn project_is_saved_and_loaded_back() {
+        let project = TranslationProject::from_json_str(PROJECT).unwrap();
+        assert_eq!(project.table, DataDispatcherType::NameTable);
+        assert_eq!(project.entries[0].status, TranslationStatus::Draft);
+        assert_eq!(project.entries[0].author.as_deref(), Some("translator"));
+        assert_eq!(project.entries[1].notes, "the little sister");
+        assert_eq!(
+            project
+                .to_json_string()
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            PROJECT.lines().collect::<Vec<_>>()
+        );
+
+        let path = std::env::temp_dir().join(format!("project-{}.json", std::process::id()));
+        fs::write(&path, project.to_json_string().unwrap()).unwrap();
+        let loaded = TranslationProject::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), project);
+
+        assert!(TranslationProject::from_json_str(r#"{"table": "name-table"}"#).is_err());
+        assert!(TranslationProject::load("missing-project.json").is_err());
+    }
+
+    #[test]
+    fn entries_not_ready_keep_the_original_bytes() {
+        // japanese original patched as GBK: the kana of the draft can not be encoded.
+        let original = [
+            TextEncoding::Cp932.encode("お兄さん").unwrap(),
+            TextEncoding::Cp932.encode("結希").unwrap(),
+        ];
+        let container = name_table_bytes(&original);
+        let mut data = DataDispatcher::NameTable(
+            NameTable::from_bytes(&container, TextEncoding::Gbk).unwrap(),
+        );
+
+        let project = TranslationProject::from_json_str(PROJECT).unwrap();
+        let summary = project.apply(&mut data).unwrap();
+        assert_eq!(
+            (
+                summary.translated,
+                summary.untranslated,
+                summary.unknown_keys
+            ),
+            (1, 1, vec!["9".to_string()])
+        );
+        assert_eq!(
+            data.patch(&container, TextEncoding::Gbk).unwrap(),
+            name_table_bytes(&[
+                original[0].clone(),
+                TextEncoding::Gbk.encode("结希").unwrap()
+            ])
+        );
+    }
+}
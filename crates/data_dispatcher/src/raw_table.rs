@@ -0,0 +1,312 @@
+use crate::{
+    DispatcherError, DispatcherResult, FieldKind, SerializePatch, derive_support,
+    scan::looks_negated,
+    text::{DecodeIssue, TextEncoding},
+};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read, Seek, Write};
+use utils::{ReadBinary, WriteBinary};
+
+/// layout of a table the crate doesn't know yet, read by [RawTable].
+///
+/// ```{text}
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |     HEADER    |  ..CNT..  |LEN|
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |    DATA   |LEN|      DATA     |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 0-7: header, the magic;
+/// 8-: `header_length` bytes, with the item count at `count_offset`;
+/// then: items, u16 little-endian length followed by the data.
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawTableConfig {
+    pub magic: String,
+    /// type of the item count field.
+    pub count: FieldKind,
+    /// offset of the item count from the end of the magic header.
+    pub count_offset: usize,
+    /// bytes between the magic header and the first item, the item count included.
+    pub header_length: usize,
+}
+
+impl RawTableConfig {
+    /// a header made of the item count only.
+    pub fn new(magic: impl Into<String>, count: FieldKind) -> Self {
+        Self {
+            magic: magic.into(),
+            count,
+            count_offset: 0,
+            header_length: count.size().unwrap_or_default() as usize,
+        }
+    }
+
+    pub fn magic_header(&self) -> &[u8] {
+        self.magic.as_bytes()
+    }
+
+    pub fn validate(&self) -> DispatcherResult<()> {
+        let invalid = |reason: &str| {
+            Err(DispatcherError::InvalidSchema {
+                name: self.magic.clone(),
+                reason: reason.to_string(),
+            })
+        };
+        if self.magic.is_empty() {
+            return invalid("the magic header is empty");
+        }
+        match self.count.size() {
+            None => invalid("the item count must be an integer"),
+            Some(size) if self.count_offset + size as usize > self.header_length => {
+                invalid("the item count does not fit in the header")
+            }
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct RawTableItem {
+    length: u16,
+    data: String,
+}
+
+impl RawTableItem {
+    /// length of the data as read from the binary file, recomputed when serializing.
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    pub fn set_data(&mut self, data: impl Into<String>) {
+        self.data = data.into();
+    }
+}
+
+/// table of u16 length-prefixed strings whose layout is given by a [RawTableConfig].
+///
+/// whether the data is negated (like [crate::StringTable] and [crate::FileNameTable]) or
+/// plain (like [crate::NameTable]) is detected when reading and kept in the dump.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct RawTable {
+    config: RawTableConfig,
+    /// header bytes after the magic header, the item count is recomputed when serializing.
+    header: Vec<u8>,
+    negated: bool,
+    items: Vec<RawTableItem>,
+}
+
+impl RawTable {
+    pub fn config(&self) -> &RawTableConfig {
+        &self.config
+    }
+
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    pub fn negated(&self) -> bool {
+        self.negated
+    }
+
+    pub fn items(&self) -> &[RawTableItem] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut Vec<RawTableItem> {
+        &mut self.items
+    }
+
+    /// read a table laid out as `config` from the current position of `reader`.
+    pub fn deserialize_patch<R: Read + Seek>(
+        config: &RawTableConfig,
+        reader: &mut R,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<Self> {
+        config.validate()?;
+        let table = config.magic.as_str();
+        let in_header = derive_support::in_header(table, reader.stream_position()?);
+
+        reader
+            .read_magic("header", config.magic_header())
+            .map_err(in_header)?;
+        let header = reader
+            .read_bytes("header", config.header_length)
+            .map_err(in_header)?;
+        let count = config.count.read_int(
+            &mut Cursor::new(&header[config.count_offset..]),
+            "item count",
+        )?;
+
+        let raw_items = derive_support::read_items_with(reader, table, count as usize, |reader| {
+            Ok(reader.read_prefixed::<u16>("data")?)
+        })?;
+        let negated = looks_negated(
+            &raw_items
+                .iter()
+                .map(|(_, raw_data)| raw_data.as_slice())
+                .collect::<Vec<_>>(),
+            encoding,
+        );
+        let items = raw_items
+            .into_iter()
+            .map(|(length, mut raw_data)| {
+                if negated {
+                    derive_support::negate(&mut raw_data);
+                }
+                RawTableItem {
+                    length,
+                    data: encoding.decode(&raw_data),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            config: config.clone(),
+            header,
+            negated,
+            items,
+        })
+    }
+
+    /// items whose data could not be fully decoded, `base_offset` is the offset of the magic header.
+    pub fn decode_issues(&self, base_offset: u64) -> Vec<DecodeIssue> {
+        let mut offset = base_offset + (self.config.magic.len() + self.header.len()) as u64;
+        self.items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let issue = DecodeIssue::check(&self.config.magic, index, None, offset, &item.data);
+                offset += 2 + item.length as u64;
+                issue
+            })
+            .collect()
+    }
+}
+
+impl SerializePatch for RawTable {
+    fn serialize_patch<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<()> {
+        let config = &self.config;
+        config.validate()?;
+        if self.header.len() != config.header_length {
+            return Err(DispatcherError::InvalidFieldValue {
+                field: "header".to_string(),
+                reason: format!("expected {} bytes", config.header_length),
+            });
+        }
+        let count_end = config.count_offset + config.count.size().unwrap_or_default() as usize;
+
+        writer.write_bytes("header", config.magic_header())?;
+        writer.write_bytes("header", &self.header[..config.count_offset])?;
+        config
+            .count
+            .write_count(writer, &config.magic, "item count", self.items.len())?;
+        writer.write_bytes("header", &self.header[count_end..])?;
+
+        derive_support::write_items_with(writer, &config.magic, &self.items, |writer, item| {
+            let mut raw_data = encoding.encode(&item.data)?;
+            if self.negated {
+                derive_support::negate(&mut raw_data);
+            }
+            writer.write_prefixed::<u16>("data", &raw_data)?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RawTableConfig {
+        RawTableConfig::new("[MESTST]", FieldKind::U16)
+    }
+
+    fn table(texts: &[&[u8]], negated: bool) -> Vec<u8> {
+        let mut bytes = b"[MESTST]".to_vec();
+        bytes.extend((texts.len() as u16).to_le_bytes());
+        for text in texts {
+            let mut raw_data = text.to_vec();
+            if negated {
+                derive_support::negate(&mut raw_data);
+            }
+            bytes.extend((raw_data.len() as u16).to_le_bytes());
+            bytes.extend(raw_data);
+        }
+        bytes
+    }
+
+    fn read(bytes: &[u8]) -> RawTable {
+        RawTable::deserialize_patch(&config(), &mut Cursor::new(bytes), TextEncoding::Cp932)
+            .unwrap()
+    }
+
+    fn write(table: &RawTable) -> Vec<u8> {
+        table.to_bytes(TextEncoding::Cp932).unwrap()
+    }
+
+    fn texts(table: &RawTable) -> Vec<&str> {
+        table.items().iter().map(RawTableItem::data).collect()
+    }
+
+    /// "おはよう\n" and "結希", with their nulls.
+    const JAPANESE: [&[u8]; 2] = [
+        b"\x82\xA8\x82\xCD\x82\xE6\x82\xA4\n\0",
+        b"\x8C\x8B\x8A\xF3\0\0",
+    ];
+
+    #[test]
+    fn negated_data_is_detected() {
+        let bytes = table(&JAPANESE, true);
+        let table = read(&bytes);
+        assert!(table.negated());
+        assert_eq!(texts(&table), ["おはよう\n\0", "結希\0\0"]);
+        assert_eq!(write(&table), bytes);
+    }
+
+    #[test]
+    fn plain_data_is_detected() {
+        let bytes = table(&JAPANESE, false);
+        let table = read(&bytes);
+        assert!(!table.negated());
+        assert_eq!(texts(&table), ["おはよう\n\0", "結希\0\0"]);
+        assert_eq!(write(&table), bytes);
+    }
+
+    #[test]
+    fn short_data_is_detected_from_a_single_byte() {
+        // a lone null, 0xFF is not a cp932 character.
+        assert!(read(&table(&[b"\0"], true)).negated());
+        assert!(!read(&table(&[b"\0"], false)).negated());
+        assert!(looks_negated(&[b"\xFF"], TextEncoding::Cp932));
+        assert!(!looks_negated(&[b"\0"], TextEncoding::Cp932));
+    }
+
+    #[test]
+    fn ambiguous_data_is_read_as_plain_and_kept() {
+        // nothing to decode.
+        for bytes in [table(&[], false), table(&[b"", b""], false)] {
+            let table = read(&bytes);
+            assert!(!table.negated());
+            assert_eq!(write(&table), bytes);
+        }
+
+        // negated ascii is half-width katakana, both decode cleanly.
+        let bytes = table(&[b"ABC"], true);
+        let table = read(&bytes);
+        assert!(!table.negated());
+        assert_eq!(texts(&table), ["ｾｽｼ"]);
+        assert_eq!(write(&table), bytes);
+
+        // as suspicious either way: 0x01 negated is 0xFE.
+        assert!(!looks_negated(&[b"\x01\xFE"], TextEncoding::Cp932));
+    }
+}
@@ -0,0 +1,453 @@
+use crate::{DataDispatcherType, FieldKind, text::TextEncoding, unescape_raw_byte};
+use std::fmt;
+use utils::LeInt;
+
+/// header fields tried after the magic header when guessing a layout.
+const HEADER_LAYOUTS: [&[FieldKind]; 4] = [
+    &[FieldKind::U32, FieldKind::U32],
+    &[FieldKind::U16, FieldKind::U16],
+    &[FieldKind::U32],
+    &[FieldKind::U16],
+];
+
+/// counts above this are not taken as item counts.
+const MAX_ITEM_COUNT: u64 = 0x10_0000;
+
+/// texts decoded to score a layout, the first ones of the table.
+const SAMPLED_ITEMS: usize = 256;
+
+const MAGIC_LENGTH: usize = 8;
+
+/// magic header found by [scan], with what could be guessed about its table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanHit {
+    /// offset of the magic header in the buffer.
+    pub offset: usize,
+    pub magic: String,
+    /// table type already supported with this magic header.
+    pub known: Option<DataDispatcherType>,
+    /// integers right after the magic header that could be an item count.
+    pub counts: Vec<CountGuess>,
+    /// most likely layout, none if no length-prefixed string layout fits.
+    pub layout: Option<LayoutGuess>,
+}
+
+/// integer after the magic header that could be an item count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountGuess {
+    /// offset from the magic header.
+    pub offset: usize,
+    pub kind: FieldKind,
+    pub value: u64,
+}
+
+/// layout of a table of length-prefixed strings guessed by [scan].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutGuess {
+    /// fields between the magic header and the first item.
+    pub header: Vec<FieldKind>,
+    /// index of the item count in `header`.
+    pub count_index: usize,
+    pub item_count: usize,
+    /// items start with a u32 id, like the string table.
+    pub has_id: bool,
+    /// texts are bitwise negated.
+    pub negated: bool,
+    /// every text ends with a null.
+    pub null_terminated: bool,
+    /// items are padded to a multiple of this, 1 for no padding.
+    pub align: u64,
+    /// size of the whole table, magic header included.
+    pub length: usize,
+    /// characters of the sampled texts, and how many of them are undecodable bytes or
+    /// control characters.
+    pub sampled_chars: usize,
+    pub suspicious_chars: usize,
+}
+
+/// find every `[......]` style magic header in `buffer` and guess the layout of its table.
+///
+/// magic headers inside a table whose layout was guessed are skipped, texts are decoded
+/// with `encoding` to tell negated data from plain data.
+pub fn scan(buffer: &[u8], encoding: TextEncoding) -> Vec<ScanHit> {
+    let mut hits: Vec<ScanHit> = vec![];
+    let mut table_end = 0;
+    for offset in 0..buffer.len().saturating_sub(MAGIC_LENGTH - 1) {
+        let magic = &buffer[offset..offset + MAGIC_LENGTH];
+        if offset < table_end || !is_magic(magic) {
+            continue;
+        }
+
+        let table = &buffer[offset..];
+        let layout = guess_layout(table, encoding);
+        if let Some(layout) = &layout {
+            table_end = offset + layout.length;
+        }
+        hits.push(ScanHit {
+            offset,
+            magic: String::from_utf8_lossy(magic).into_owned(),
+            known: DataDispatcherType::ALL
+                .into_iter()
+                .find(|patch_type| patch_type.magic_header() == magic),
+            counts: guess_counts(table),
+            layout,
+        });
+    }
+    hits
+}
+
+/// `[` and `]` around six ascii letters, digits, `-` or `_`.
+fn is_magic(magic: &[u8]) -> bool {
+    magic[0] == b'['
+        && magic[MAGIC_LENGTH - 1] == b']'
+        && magic[1..MAGIC_LENGTH - 1]
+            .iter()
+            .all(|&byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+/// little-endian integer at `pos` of `data`, none past the end or for texts.
+pub(crate) fn read_int(data: &[u8], pos: usize, kind: FieldKind) -> Option<u64> {
+    fn read<T: LeInt + Into<u64>>(data: &[u8], pos: usize) -> Option<u64> {
+        let bytes = data.get(pos..pos.checked_add(T::SIZE)?)?;
+        Some(T::from_le_slice(bytes).into())
+    }
+    match kind {
+        FieldKind::U8 => read::<u8>(data, pos),
+        FieldKind::U16 => read::<u16>(data, pos),
+        FieldKind::U32 => read::<u32>(data, pos),
+        FieldKind::U64 => read::<u64>(data, pos),
+        FieldKind::Text => None,
+    }
+}
+
+fn guess_counts(table: &[u8]) -> Vec<CountGuess> {
+    [
+        (8, FieldKind::U16),
+        (10, FieldKind::U16),
+        (8, FieldKind::U32),
+        (12, FieldKind::U32),
+    ]
+    .into_iter()
+    .filter_map(|(offset, kind)| {
+        let value = read_int(table, offset, kind)?;
+        (1..=MAX_ITEM_COUNT).contains(&value).then_some(CountGuess {
+            offset,
+            kind,
+            value,
+        })
+    })
+    .collect()
+}
+
+/// raw texts of the table read with a layout, and the item sizes.
+struct ParsedItems<'a> {
+    texts: Vec<&'a [u8]>,
+    item_sizes: Vec<usize>,
+    end: usize,
+}
+
+fn parse_items<'a>(
+    table: &'a [u8],
+    header: &[FieldKind],
+    count_index: usize,
+    has_id: bool,
+) -> Option<ParsedItems<'a>> {
+    let mut pos = MAGIC_LENGTH;
+    let mut count = 0;
+    for (index, &kind) in header.iter().enumerate() {
+        let value = read_int(table, pos, kind)?;
+        if index == count_index {
+            count = value;
+        }
+        pos += kind.size()? as usize;
+    }
+    if !(1..=MAX_ITEM_COUNT).contains(&count) {
+        return None;
+    }
+
+    let mut parsed = ParsedItems {
+        texts: vec![],
+        item_sizes: vec![],
+        end: pos,
+    };
+    for _ in 0..count {
+        let item_pos = pos;
+        if has_id {
+            pos += 4;
+        }
+        let length = read_int(table, pos, FieldKind::U16)? as usize;
+        pos += 2;
+        parsed.texts.push(table.get(pos..pos + length)?);
+        pos += length;
+        parsed.item_sizes.push(pos - item_pos);
+    }
+    parsed.end = pos;
+    Some(parsed)
+}
+
+/// undecodable bytes and control characters other than line breaks and nulls.
+fn count_suspicious(texts: &[&[u8]], negated: bool, encoding: TextEncoding) -> (usize, usize) {
+    let mut chars = 0;
+    let mut suspicious = 0;
+    for text in texts.iter().take(SAMPLED_ITEMS) {
+        let mut raw_data = text.to_vec();
+        if negated {
+            raw_data.iter_mut().for_each(|byte| *byte = !*byte);
+        }
+        for ch in encoding.decode(&raw_data).chars() {
+            chars += 1;
+            let control = ch.is_control() && !matches!(ch, '\n' | '\r' | '\t' | '\0');
+            if control || unescape_raw_byte(ch).is_some() {
+                suspicious += 1;
+            }
+        }
+    }
+    (chars, suspicious)
+}
+
+/// the texts decode better once negated.
+pub(crate) fn looks_negated(texts: &[&[u8]], encoding: TextEncoding) -> bool {
+    let plain = count_suspicious(texts, false, encoding);
+    let negated = count_suspicious(texts, true, encoding);
+    // compare the share of suspicious characters, without floats.
+    negated.1 * plain.0 < plain.1 * negated.0
+}
+
+fn guess_layout(table: &[u8], encoding: TextEncoding) -> Option<LayoutGuess> {
+    let mut guesses = vec![];
+    for header in HEADER_LAYOUTS {
+        for count_index in 0..header.len() {
+            for has_id in [false, true] {
+                let Some(parsed) = parse_items(table, header, count_index, has_id) else {
+                    continue;
+                };
+                let is_negated = looks_negated(&parsed.texts, encoding);
+                let (sampled_chars, suspicious_chars) =
+                    count_suspicious(&parsed.texts, is_negated, encoding);
+                let null = if is_negated { 0xFF } else { 0x00 };
+                let null_terminated = parsed.texts.iter().all(|text| text.last() == Some(&null));
+                let align = match null_terminated
+                    && parsed.item_sizes.len() > 1
+                    && parsed.item_sizes.iter().all(|size| size % 4 == 0)
+                {
+                    true => 4,
+                    false => 1,
+                };
+                guesses.push(LayoutGuess {
+                    header: header.to_vec(),
+                    count_index,
+                    item_count: parsed.texts.len(),
+                    has_id,
+                    negated: is_negated,
+                    null_terminated,
+                    align,
+                    length: parsed.end,
+                    sampled_chars,
+                    suspicious_chars,
+                });
+            }
+        }
+    }
+
+    // the fewest suspicious characters, then the longest header and items as they explain
+    // more of the data.
+    guesses.into_iter().min_by(|a, b| {
+        (a.suspicious_chars * b.sampled_chars.max(1))
+            .cmp(&(b.suspicious_chars * a.sampled_chars.max(1)))
+            .then(b.header.len().cmp(&a.header.len()))
+            .then(b.has_id.cmp(&a.has_id))
+    })
+}
+
+fn kind_name(kind: FieldKind) -> &'static str {
+    match kind {
+        FieldKind::U8 => "u8",
+        FieldKind::U16 => "u16",
+        FieldKind::U32 => "u32",
+        FieldKind::U64 => "u64",
+        FieldKind::Text => "text",
+    }
+}
+
+impl fmt::Display for CountGuess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at +{:#x} = {}",
+            kind_name(self.kind),
+            self.offset,
+            self.value
+        )
+    }
+}
+
+impl fmt::Display for LayoutGuess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = self
+            .header
+            .iter()
+            .map(|&kind| kind_name(kind))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "header ({header}) with the count in field {}, {} items of ",
+            self.count_index, self.item_count
+        )?;
+        if self.has_id {
+            f.write_str("u32 id + ")?;
+        }
+        let negated = if self.negated { "negated" } else { "plain" };
+        write!(f, "u16 length-prefixed {negated} text")?;
+        if self.null_terminated {
+            f.write_str(", null terminated")?;
+        }
+        if self.align > 1 {
+            write!(f, ", padded to {} bytes", self.align)?;
+        }
+        write!(
+            f,
+            ", {:#x} bytes, {}/{} suspicious characters",
+            self.length, self.suspicious_chars, self.sampled_chars
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negated(data: &[u8]) -> Vec<u8> {
+        data.iter().map(|byte| !byte).collect()
+    }
+
+    /// "おはよう\n" with id 1 and "結希" with id 2.
+    fn string_table() -> Vec<u8> {
+        let mut bytes = b"[STRTBL]".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        for (id, data) in [
+            (1u32, &b"\x82\xA8\x82\xCD\x82\xE6\x82\xA4\n\0"[..]),
+            (2, b"\x8C\x8B\x8A\xF3\0\0"),
+        ] {
+            bytes.extend(id.to_le_bytes());
+            bytes.extend((data.len() as u16).to_le_bytes());
+            bytes.extend(negated(data));
+        }
+        bytes
+    }
+
+    /// "結希" and "お兄さん", with a magic header lookalike in the second text.
+    fn name_table() -> Vec<u8> {
+        let mut bytes = b"[MESNAM]".to_vec();
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend(3u16.to_le_bytes());
+        for data in [
+            &b"\x8C\x8B\x8A\xF3"[..],
+            b"\x82\xA8\x8C\x5A\x82\xB3\x82\xF1",
+            b"[MESNAM]",
+        ] {
+            bytes.extend((data.len() as u16).to_le_bytes());
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn embedded_tables_are_found_at_their_offset() {
+        let mut buffer = b"DARC\0\0".to_vec();
+        let string_offset = buffer.len();
+        buffer.extend(string_table());
+        buffer.extend([0; 10]);
+        let name_offset = buffer.len();
+        buffer.extend(name_table());
+        buffer.extend(b"\0\0\0");
+
+        let hits = scan(&buffer, TextEncoding::Cp932);
+        let found: Vec<_> = hits
+            .iter()
+            .map(|hit| (hit.offset, hit.magic.as_str(), hit.known))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    string_offset,
+                    "[STRTBL]",
+                    Some(DataDispatcherType::StringTable)
+                ),
+                (name_offset, "[MESNAM]", Some(DataDispatcherType::NameTable)),
+            ]
+        );
+
+        let layout = hits[0].layout.as_ref().unwrap();
+        assert_eq!(
+            (
+                layout.header.as_slice(),
+                layout.count_index,
+                layout.item_count
+            ),
+            (&[FieldKind::U32, FieldKind::U32][..], 0, 2)
+        );
+        assert!(layout.has_id && layout.negated && layout.null_terminated);
+        assert_eq!((layout.align, layout.length), (4, string_table().len()));
+        assert_eq!(layout.suspicious_chars, 0);
+
+        let layout = hits[1].layout.as_ref().unwrap();
+        assert_eq!(
+            (
+                layout.header.as_slice(),
+                layout.count_index,
+                layout.item_count
+            ),
+            (&[FieldKind::U16, FieldKind::U16][..], 1, 3)
+        );
+        assert!(!layout.has_id && !layout.negated && !layout.null_terminated);
+        assert_eq!((layout.align, layout.length), (1, name_table().len()));
+        assert!(hits[1].counts.contains(&CountGuess {
+            offset: 10,
+            kind: FieldKind::U16,
+            value: 3,
+        }));
+    }
+
+    #[test]
+    fn truncated_tables_have_no_layout() {
+        let table = name_table();
+        let hits = scan(&table[..table.len() - 1], TextEncoding::Cp932);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].layout, None);
+        assert_eq!(hits[0].counts[0].value, 3);
+
+        // only a layout reading fewer items, with the second header field as the count,
+        // still fits in the truncated string table.
+        let table = string_table();
+        let hits = scan(&table[..table.len() - 1], TextEncoding::Cp932);
+        let layout = hits[0].layout.as_ref().unwrap();
+        assert_eq!((layout.count_index, layout.item_count), (1, 1));
+        assert!(layout.length < table.len() - 1);
+
+        // the magic header itself is cut.
+        assert_eq!(scan(&table[..7], TextEncoding::Cp932), []);
+        let hits = scan(&table[..8], TextEncoding::Cp932);
+        assert_eq!((hits.len(), hits[0].counts.len()), (1, 0));
+        assert_eq!(hits[0].layout, None);
+    }
+
+    #[test]
+    fn magic_lookalikes_are_not_tables() {
+        for buffer in [
+            &b"[STR TB]\x01\0\0\0"[..],
+            b"[STRTB]\x01\0\0\0",
+            b"(STRTBL)\x01\0\0\0",
+        ] {
+            assert_eq!(scan(buffer, TextEncoding::Cp932), []);
+        }
+
+        // bracketed like a magic header, without any count after it.
+        let hits = scan(b"[PREFIX]\0\0\0\0\0\0\0\0", TextEncoding::Cp932);
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].known, hits[0].counts.len()), (None, 0));
+        assert_eq!(hits[0].layout, None);
+    }
+}
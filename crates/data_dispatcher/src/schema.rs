@@ -0,0 +1,756 @@
+use crate::{
+    DispatcherError, DispatcherResult, SerializePatch, derive_support,
+    text::{DecodeIssue, TextEncoding},
+};
+use clap::ValueEnum;
+use ron::extensions::Extensions;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{Read, Seek, Write},
+    path::Path,
+};
+use utils::{ReadBinary, WriteBinary};
+
+/// built-in schemas, equivalent to the hard-coded tables.
+const BUILTIN_SCHEMAS: [&str; 3] = [
+    include_str!("../schemas/string_table.ron"),
+    include_str!("../schemas/name_table.ron"),
+    include_str!("../schemas/file_name_table.ron"),
+];
+
+/// layout of a table described by an external schema file, so that tables of other titles
+/// can be parsed and patched without recompiling.
+///
+/// the table is the magic header, the header fields, then as many items as the `count`
+/// header field, each made of the item fields. integers are little-endian, texts are
+/// prefixed with their length in bytes.
+///
+/// ```{text}
+/// TableSchema(
+///     name: "StringTable",
+///     magic: "[STRTBL]",
+///     header: [
+///         (name: "item_count", kind: u32, count: true),
+///         (name: "assume_magic_number", kind: u32, usual: 1),
+///     ],
+///     item: [
+///         (name: "id", kind: u32),
+///         (name: "text", kind: text, negate: true, null_terminated: true, align: 4),
+///     ],
+/// )
+/// ```
+///
+/// ## Note
+/// schema files are written in ron, or in toml when the extension is `.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableSchema {
+    pub name: String,
+    pub magic: String,
+    /// encoding of the text fields, the one given on the command line if omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<TextEncoding>,
+    pub header: Vec<FieldSchema>,
+    pub item: Vec<FieldSchema>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    pub name: String,
+    pub kind: FieldKind,
+    /// the header field holding the item count, recomputed when writing.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub count: bool,
+    /// type of the length prefix of a text field.
+    #[serde(default = "default_prefix", skip_serializing_if = "is_default_prefix")]
+    pub prefix: FieldKind,
+    /// the text bytes are bitwise negated.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub negate: bool,
+    /// the text ends with nulls, stripped when reading and written back as one null.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub null_terminated: bool,
+    /// the text is padded with nulls until the item (or the table header) is a multiple of
+    /// `align` bytes, 0 for no padding.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub align: u64,
+    /// encoding of this text field, overrides the table one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<TextEncoding>,
+    /// value of this integer field in the original tables, others are reported by
+    /// [crate::validate].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usual: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    U8,
+    U16,
+    U32,
+    U64,
+    #[value(skip)]
+    Text,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn default_prefix() -> FieldKind {
+    FieldKind::U16
+}
+
+fn is_default_prefix(prefix: &FieldKind) -> bool {
+    *prefix == FieldKind::U16
+}
+
+impl FieldKind {
+    /// size of an integer field in bytes, none for texts.
+    pub(crate) fn size(self) -> Option<u64> {
+        match self {
+            FieldKind::U8 => Some(1),
+            FieldKind::U16 => Some(2),
+            FieldKind::U32 => Some(4),
+            FieldKind::U64 => Some(8),
+            FieldKind::Text => None,
+        }
+    }
+
+    pub(crate) fn read_int<R: Read + Seek>(
+        self,
+        reader: &mut R,
+        field: &str,
+    ) -> DispatcherResult<u64> {
+        Ok(match self {
+            FieldKind::U8 => reader.read_le::<u8>(field)?.into(),
+            FieldKind::U16 => reader.read_le::<u16>(field)?.into(),
+            FieldKind::U32 => reader.read_le::<u32>(field)?.into(),
+            FieldKind::U64 => reader.read_le::<u64>(field)?,
+            FieldKind::Text => return Err(not_an_integer(field)),
+        })
+    }
+
+    pub(crate) fn write_int<W: Write + Seek>(
+        self,
+        writer: &mut W,
+        field: &str,
+        value: u64,
+    ) -> DispatcherResult<()> {
+        let out_of_range = |_| DispatcherError::InvalidFieldValue {
+            field: field.to_string(),
+            reason: format!("{value} does not fit in a {self:?}"),
+        };
+        match self {
+            FieldKind::U8 => writer.write_le(field, u8::try_from(value).map_err(out_of_range)?)?,
+            FieldKind::U16 => {
+                writer.write_le(field, u16::try_from(value).map_err(out_of_range)?)?
+            }
+            FieldKind::U32 => {
+                writer.write_le(field, u32::try_from(value).map_err(out_of_range)?)?
+            }
+            FieldKind::U64 => writer.write_le(field, value)?,
+            FieldKind::Text => return Err(not_an_integer(field)),
+        }
+        Ok(())
+    }
+
+    /// write the item count of `table`, too many items for the field type is an error.
+    pub(crate) fn write_count<W: Write + Seek>(
+        self,
+        writer: &mut W,
+        table: &str,
+        field: &str,
+        count: usize,
+    ) -> DispatcherResult<()> {
+        self.write_int(writer, field, count as u64)
+            .map_err(|e| match e {
+                DispatcherError::InvalidFieldValue { .. } => DispatcherError::TooManyItems {
+                    table: table.to_string(),
+                    count,
+                },
+                e => e,
+            })
+    }
+}
+
+fn not_an_integer(field: &str) -> DispatcherError {
+    DispatcherError::InvalidFieldValue {
+        field: field.to_string(),
+        reason: "expected an integer field".to_string(),
+    }
+}
+
+impl TableSchema {
+    /// the built-in schemas of the `StringTable`, `NameTable` and `FileNameTable` tables.
+    pub fn builtin() -> Vec<TableSchema> {
+        BUILTIN_SCHEMAS
+            .iter()
+            .map(|schema| Self::from_ron_str(schema).expect("built-in schemas are valid"))
+            .collect()
+    }
+
+    pub fn is_builtin(name: &str) -> bool {
+        Self::builtin().iter().any(|schema| schema.name == name)
+    }
+
+    /// built-in schema named `name_or_path`, or else the schema file at this path.
+    pub fn find(name_or_path: &str) -> DispatcherResult<Self> {
+        match Self::builtin()
+            .into_iter()
+            .find(|schema| schema.name == name_or_path)
+        {
+            Some(schema) => Ok(schema),
+            None => Self::load(name_or_path),
+        }
+    }
+
+    /// load a schema file, toml if the extension is `.toml` and ron otherwise.
+    pub fn load<P: AsRef<Path>>(path: P) -> DispatcherResult<Self> {
+        let path = path.as_ref();
+        let is_toml = path
+            .extension()
+            .is_some_and(|extension| extension == "toml");
+        fs::read_to_string(path)
+            .map_err(DispatcherError::from)
+            .and_then(|schema_string| match is_toml {
+                true => Self::from_toml_str(&schema_string),
+                false => Self::from_ron_str(&schema_string),
+            })
+            .map_err(|e| DispatcherError::LoadFailed {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })
+    }
+
+    pub fn from_ron_str(schema_string: &str) -> DispatcherResult<Self> {
+        let schema: Self = ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_str(schema_string)?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    pub fn from_toml_str(schema_string: &str) -> DispatcherResult<Self> {
+        let schema: Self = toml::from_str(schema_string)?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    pub fn magic_header(&self) -> &[u8] {
+        self.magic.as_bytes()
+    }
+
+    /// check the layout can be read and written back.
+    pub fn validate(&self) -> DispatcherResult<()> {
+        let invalid = |reason: String| {
+            Err(DispatcherError::InvalidSchema {
+                name: self.name.clone(),
+                reason,
+            })
+        };
+
+        if self.magic.is_empty() {
+            return invalid("the magic header is empty".to_string());
+        }
+        if self.item.is_empty() {
+            return invalid("items have no field".to_string());
+        }
+        match self.header.iter().filter(|field| field.count).count() {
+            1 => {}
+            0 => return invalid("no header field is marked as `count`".to_string()),
+            _ => return invalid("several header fields are marked as `count`".to_string()),
+        }
+
+        for fields in [&self.header, &self.item] {
+            let mut names = BTreeSet::new();
+            for field in fields {
+                if !names.insert(field.name.as_str()) {
+                    return invalid(format!("field `{}` is declared twice", field.name));
+                }
+                if field.kind == FieldKind::Text {
+                    if field.prefix.size().is_none() {
+                        return invalid(format!("`{}` has a text length prefix", field.name));
+                    }
+                    if field.count {
+                        return invalid(format!("count field `{}` is a text", field.name));
+                    }
+                    if field.usual.is_some() {
+                        return invalid(format!(
+                            "`{}` is a text, `usual` only applies to integers",
+                            field.name
+                        ));
+                    }
+                } else if field.negate
+                    || field.null_terminated
+                    || field.align != 0
+                    || field.encoding.is_some()
+                {
+                    return invalid(format!(
+                        "`{}` is an integer, `negate`, `null_terminated`, `align` and \
+                         `encoding` only apply to texts",
+                        field.name
+                    ));
+                }
+            }
+        }
+        if self.item.iter().any(|field| field.count) {
+            return invalid("only header fields can be marked as `count`".to_string());
+        }
+        Ok(())
+    }
+
+    fn field_encoding(&self, field: &FieldSchema, encoding: TextEncoding) -> TextEncoding {
+        field.encoding.or(self.encoding).unwrap_or(encoding)
+    }
+
+    fn read_field<R: Read + Seek>(
+        &self,
+        field: &FieldSchema,
+        reader: &mut R,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<FieldValue> {
+        if field.kind != FieldKind::Text {
+            return Ok(FieldValue::Int(field.kind.read_int(reader, &field.name)?));
+        }
+
+        let length = field.prefix.read_int(reader, &field.name)?;
+        let mut raw_data = reader.read_bytes(&field.name, length as usize)?;
+        if field.negate {
+            derive_support::negate(&mut raw_data);
+        }
+        let mut text = self.field_encoding(field, encoding).decode(&raw_data);
+        if field.null_terminated {
+            text.truncate(text.trim_end_matches('\0').len());
+        }
+        Ok(FieldValue::Text(text))
+    }
+
+    /// write a field of the block (table or item) starting at `block_pos`.
+    fn write_field<W: Write + Seek>(
+        &self,
+        field: &FieldSchema,
+        value: Option<&FieldValue>,
+        writer: &mut W,
+        encoding: TextEncoding,
+        block_pos: u64,
+    ) -> DispatcherResult<()> {
+        let invalid = |reason: &str| DispatcherError::InvalidFieldValue {
+            field: field.name.clone(),
+            reason: reason.to_string(),
+        };
+
+        match (field.kind, value) {
+            (_, None) => Err(invalid("missing field")),
+            (FieldKind::Text, Some(FieldValue::Text(text))) => {
+                let encoding = self.field_encoding(field, encoding);
+                let mut raw_data = match field.null_terminated {
+                    true => {
+                        let mut raw_data = encoding.encode(text.trim_end_matches('\0'))?;
+                        raw_data.push(0);
+                        raw_data
+                    }
+                    false => encoding.encode(text)?,
+                };
+                if field.align != 0 {
+                    let prefix_size = field.prefix.size().unwrap_or_default();
+                    let head_length = writer.stream_position()? - block_pos + prefix_size;
+                    derive_support::pad_to_alignment(&mut raw_data, head_length, field.align);
+                }
+                if field.negate {
+                    derive_support::negate(&mut raw_data);
+                }
+                field
+                    .prefix
+                    .write_int(writer, &field.name, raw_data.len() as u64)?;
+                writer.write_bytes(&field.name, &raw_data)?;
+                Ok(())
+            }
+            (FieldKind::Text, Some(FieldValue::Int(_))) => Err(invalid("expected a text")),
+            (kind, Some(FieldValue::Int(value))) => kind.write_int(writer, &field.name, *value),
+            (_, Some(FieldValue::Text(_))) => Err(invalid("expected an integer")),
+        }
+    }
+}
+
+/// value of a field of a [SchemaTable].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Int(u64),
+    Text(String),
+}
+
+/// item of a [SchemaTable], its fields by name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SchemaItem {
+    fields: BTreeMap<String, FieldValue>,
+    /// offset of the item from the magic header, only known for parsed tables.
+    #[serde(skip)]
+    offset: Option<u64>,
+}
+
+impl PartialEq for SchemaItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields == other.fields
+    }
+}
+
+impl Eq for SchemaItem {}
+
+impl SchemaItem {
+    pub fn get(&self, field: &str) -> Option<&FieldValue> {
+        self.fields.get(field)
+    }
+
+    pub fn set(&mut self, field: &str, value: FieldValue) {
+        self.fields.insert(field.to_string(), value);
+    }
+
+    pub fn fields(&self) -> &BTreeMap<String, FieldValue> {
+        &self.fields
+    }
+}
+
+/// table read with a [TableSchema], the schema is kept in the dump so the patcher does
+/// not need it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaTable {
+    schema: TableSchema,
+    header: BTreeMap<String, FieldValue>,
+    items: Vec<SchemaItem>,
+}
+
+impl SchemaTable {
+    pub fn schema(&self) -> &TableSchema {
+        &self.schema
+    }
+
+    pub fn header(&self) -> &BTreeMap<String, FieldValue> {
+        &self.header
+    }
+
+    pub fn items(&self) -> &[SchemaItem] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut Vec<SchemaItem> {
+        &mut self.items
+    }
+
+    /// read a table laid out as `schema` from the current position of `reader`.
+    pub fn deserialize_patch<R: Read + Seek>(
+        schema: &TableSchema,
+        reader: &mut R,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<Self> {
+        schema.validate()?;
+        let start_pos = reader.stream_position()?;
+        let in_header = derive_support::in_header(&schema.name, start_pos);
+        let in_header = |e| match e {
+            DispatcherError::Binary(e) => in_header(e),
+            e => e,
+        };
+
+        reader
+            .read_magic("header", schema.magic_header())
+            .map_err(DispatcherError::from)
+            .map_err(in_header)?;
+        let mut header = BTreeMap::new();
+        let mut count = 0;
+        for field in &schema.header {
+            let value = schema
+                .read_field(field, reader, encoding)
+                .map_err(in_header)?;
+            if let (true, FieldValue::Int(value)) = (field.count, &value) {
+                count = *value as usize;
+            }
+            header.insert(field.name.clone(), value);
+        }
+
+        let items = derive_support::read_items_with(reader, &schema.name, count, |reader| {
+            let mut item = SchemaItem {
+                offset: Some(reader.stream_position()? - start_pos),
+                ..Default::default()
+            };
+            for field in &schema.item {
+                let value = schema.read_field(field, reader, encoding)?;
+                item.fields.insert(field.name.clone(), value);
+            }
+            Ok(item)
+        })?;
+
+        Ok(Self {
+            schema: schema.clone(),
+            header,
+            items,
+        })
+    }
+
+    /// items whose texts could not be fully decoded, `base_offset` is the offset of the
+    /// magic header. the `id` field is reported as the item id if there is one.
+    pub fn decode_issues(&self, base_offset: u64) -> Vec<DecodeIssue> {
+        let mut issues = vec![];
+        for (index, item) in self.items.iter().enumerate() {
+            let id = match item.get("id") {
+                Some(FieldValue::Int(id)) => u32::try_from(*id).ok(),
+                _ => None,
+            };
+            let offset = base_offset + item.offset.unwrap_or_default();
+            issues.extend(item.fields.values().filter_map(|value| match value {
+                FieldValue::Text(text) => {
+                    DecodeIssue::check(&self.schema.name, index, id, offset, text)
+                }
+                FieldValue::Int(_) => None,
+            }));
+        }
+        issues
+    }
+}
+
+impl SerializePatch for SchemaTable {
+    fn serialize_patch<W: Write + Seek>(
+        &self,
+        writer: &mut W,
+        encoding: TextEncoding,
+    ) -> DispatcherResult<()> {
+        let schema = &self.schema;
+        schema.validate()?;
+        let start_pos = writer.stream_position()?;
+
+        writer.write_bytes("header", schema.magic_header())?;
+        for field in &schema.header {
+            match field.count {
+                true => {
+                    field
+                        .kind
+                        .write_count(writer, &schema.name, &field.name, self.items.len())?
+                }
+                false => schema.write_field(
+                    field,
+                    self.header.get(&field.name),
+                    writer,
+                    encoding,
+                    start_pos,
+                )?,
+            }
+        }
+
+        derive_support::write_items_with(writer, &schema.name, &self.items, |writer, item| {
+            let item_pos = writer.stream_position()?;
+            for field in &schema.item {
+                schema.write_field(field, item.get(&field.name), writer, encoding, item_pos)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const STRING_TABLE_TOML: &str = r#"
+name = "StringTable"
+magic = "[STRTBL]"
+header = [
+    { name = "item_count", kind = "u32", count = true },
+    { name = "assume_magic_number", kind = "u32", usual = 1 },
+]
+item = [
+    { name = "id", kind = "u32" },
+    { name = "text", kind = "text", negate = true, null_terminated = true, align = 4 },
+]
+"#;
+
+    fn negated(data: &[u8]) -> Vec<u8> {
+        data.iter().map(|byte| !byte).collect()
+    }
+
+    /// "AB\n" with id 7 and "結希" with id 8, padded to 4 bytes.
+    fn string_table() -> Vec<u8> {
+        let mut bytes = b"[STRTBL]".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        for (id, data) in [(7u32, &b"AB\n\0\0\0"[..]), (8, b"\x8C\x8B\x8A\xF3\0\0")] {
+            bytes.extend(id.to_le_bytes());
+            bytes.extend((data.len() as u16).to_le_bytes());
+            bytes.extend(negated(data));
+        }
+        bytes
+    }
+
+    fn name_table() -> Vec<u8> {
+        let mut bytes = b"[MESNAM]".to_vec();
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend(2u16.to_le_bytes());
+        for data in [&b"\x8C\x8B\x8A\xF3"[..], b"Yuki"] {
+            bytes.extend((data.len() as u16).to_le_bytes());
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    fn file_name_table() -> Vec<u8> {
+        let mut bytes = b"[F-NAME]".to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(9u16.to_le_bytes());
+        bytes.extend(negated(b"bg01.png\0"));
+        bytes
+    }
+
+    fn read(schema: &TableSchema, bytes: &[u8]) -> SchemaTable {
+        SchemaTable::deserialize_patch(schema, &mut Cursor::new(bytes), TextEncoding::Cp932)
+            .unwrap()
+    }
+
+    fn text(item: &SchemaItem, field: &str) -> String {
+        match item.get(field) {
+            Some(FieldValue::Text(text)) => text.clone(),
+            value => panic!("{field} is {value:?}"),
+        }
+    }
+
+    #[test]
+    fn builtin_schemas_load_from_ron_and_toml() {
+        let builtin = TableSchema::builtin();
+        let names: Vec<_> = builtin.iter().map(|schema| schema.name.as_str()).collect();
+        assert_eq!(names, ["StringTable", "NameTable", "FileNameTable"]);
+        assert!(TableSchema::is_builtin("NameTable"));
+        assert_eq!(TableSchema::find("FileNameTable").unwrap(), builtin[2]);
+
+        let from_toml = TableSchema::from_toml_str(STRING_TABLE_TOML).unwrap();
+        assert_eq!(from_toml, builtin[0]);
+        assert_eq!(from_toml.item[1].prefix, FieldKind::U16);
+
+        let dir = std::env::temp_dir().join(format!("schema-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("table.ron"), BUILTIN_SCHEMAS[0]).unwrap();
+        fs::write(dir.join("table.toml"), STRING_TABLE_TOML).unwrap();
+        let from_ron_file = TableSchema::load(dir.join("table.ron"));
+        let from_toml_file = TableSchema::find(dir.join("table.toml").to_str().unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(from_ron_file.unwrap(), builtin[0]);
+        assert_eq!(from_toml_file.unwrap(), builtin[0]);
+    }
+
+    #[test]
+    fn builtin_schemas_read_and_write_the_tables() {
+        let [string_schema, name_schema, file_name_schema] =
+            <[TableSchema; 3]>::try_from(TableSchema::builtin()).unwrap();
+
+        let bytes = string_table();
+        let table = read(&string_schema, &bytes);
+        assert_eq!(table.header()["assume_magic_number"], FieldValue::Int(1));
+        assert_eq!(table.items()[1].get("id"), Some(&FieldValue::Int(8)));
+        assert_eq!(text(&table.items()[0], "text"), "AB\n");
+        assert_eq!(text(&table.items()[1], "text"), "結希");
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), bytes);
+
+        let bytes = name_table();
+        let table = read(&name_schema, &bytes);
+        assert_eq!(table.header()["item_count"], FieldValue::Int(2));
+        assert_eq!(text(&table.items()[0], "data"), "結希");
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), bytes);
+
+        let bytes = file_name_table();
+        let table = read(&file_name_schema, &bytes);
+        assert_eq!(text(&table.items()[0], "data"), "bg01.png\0");
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), bytes);
+    }
+
+    #[test]
+    fn edited_schema_tables_are_written_with_the_layout() {
+        let schema = &TableSchema::builtin()[0];
+        let mut table = read(schema, &string_table());
+        table.items_mut()[0].set("text", FieldValue::Text("A".to_string()));
+        table.items_mut().truncate(1);
+
+        let mut expected = b"[STRTBL]".to_vec();
+        expected.extend(1u32.to_le_bytes());
+        expected.extend(1u32.to_le_bytes());
+        expected.extend(7u32.to_le_bytes());
+        expected.extend(2u16.to_le_bytes());
+        expected.extend(negated(b"A\0"));
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), expected);
+
+        table.items_mut()[0].set("text", FieldValue::Int(1));
+        match table.to_bytes(TextEncoding::Cp932) {
+            Err(DispatcherError::InvalidItem { index, source, .. }) => {
+                assert_eq!(index, 0);
+                assert!(matches!(
+                    *source,
+                    DispatcherError::InvalidFieldValue { field, .. } if field == "text"
+                ));
+            }
+            result => panic!("{result:?}"),
+        }
+    }
+
+    #[test]
+    fn malformed_schemas_are_rejected() {
+        let reasons = [
+            (
+                "(name: \"T\", magic: \"\", header: [(name: \"n\", kind: u32, count: true)], \
+                 item: [(name: \"t\", kind: text)])",
+                "the magic header is empty",
+            ),
+            (
+                "(name: \"T\", magic: \"[T]\", header: [(name: \"n\", kind: u32)], \
+                 item: [(name: \"t\", kind: text)])",
+                "no header field is marked as `count`",
+            ),
+            (
+                "(name: \"T\", magic: \"[T]\", header: [(name: \"n\", kind: text, count: true)], \
+                 item: [(name: \"t\", kind: text)])",
+                "count field `n` is a text",
+            ),
+            (
+                "(name: \"T\", magic: \"[T]\", header: [(name: \"n\", kind: u32, count: true)], \
+                 item: [(name: \"t\", kind: u16, negate: true)])",
+                "`t` is an integer, `negate`, `null_terminated`, `align` and `encoding` only \
+                 apply to texts",
+            ),
+            (
+                "(name: \"T\", magic: \"[T]\", header: [(name: \"n\", kind: u32, count: true)], \
+                 item: [(name: \"t\", kind: text), (name: \"t\", kind: u8)])",
+                "field `t` is declared twice",
+            ),
+        ];
+        for (schema, expected) in reasons {
+            match TableSchema::from_ron_str(schema) {
+                Err(DispatcherError::InvalidSchema { name, reason }) => {
+                    assert_eq!((name.as_str(), reason.as_str()), ("T", expected))
+                }
+                result => panic!("{schema} gave {result:?}"),
+            }
+        }
+
+        // not a schema at all.
+        assert!(matches!(
+            TableSchema::from_ron_str("(name: \"T\", magic: \"[T]\", item: [])"),
+            Err(DispatcherError::Ron(_))
+        ));
+        assert!(matches!(
+            TableSchema::from_ron_str(&BUILTIN_SCHEMAS[0].replace("kind: u32", "kind: i32")),
+            Err(DispatcherError::Ron(_))
+        ));
+        assert!(matches!(
+            TableSchema::from_toml_str("name = \"T\"\nmagic = \"[T]\""),
+            Err(DispatcherError::Toml(_))
+        ));
+        assert!(matches!(
+            TableSchema::load("missing-schema.ron"),
+            Err(DispatcherError::LoadFailed { .. })
+        ));
+    }
+}
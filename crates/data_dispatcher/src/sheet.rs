@@ -0,0 +1,492 @@
+use crate::{
+    DataDispatcher, DataDispatcherType, DispatcherError, DispatcherResult, ImportSummary,
+    TranslationStatus,
+};
+use calamine::{Reader, Xlsx, open_workbook_from_rs};
+use encoding_rs::{DecoderResult, Encoding, UTF_8};
+use rust_xlsxwriter::{Format, Workbook};
+use std::{collections::HashMap, fs, io::Cursor, path::Path};
+
+const COLUMNS: [&str; 6] = ["id", "source", "translation", "speaker", "notes", "status"];
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// spreadsheet format of a [Sheet].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SheetFormat {
+    Csv,
+    /// tab separated, as saved by Excel as unicode text.
+    Tsv,
+    Xlsx,
+}
+
+impl SheetFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            SheetFormat::Csv => "csv",
+            SheetFormat::Tsv => "tsv",
+            SheetFormat::Xlsx => "xlsx",
+        }
+    }
+
+    fn delimiter(self) -> u8 {
+        match self {
+            SheetFormat::Tsv => b'\t',
+            SheetFormat::Csv | SheetFormat::Xlsx => b',',
+        }
+    }
+}
+
+/// row of a [Sheet], one per string table item.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SheetRow {
+    pub id: String,
+    /// original text, without the terminator and the padding.
+    pub source: String,
+    /// empty while untranslated.
+    pub translation: String,
+    /// left to the translators, the tables don't name the speakers.
+    pub speaker: String,
+    pub notes: String,
+    /// imported if empty and translated.
+    pub status: Option<TranslationStatus>,
+}
+
+impl SheetRow {
+    /// translated, and neither a draft nor marked untranslated.
+    pub fn is_ready(&self) -> bool {
+        !self.translation.is_empty() && self.status.is_none_or(TranslationStatus::is_ready)
+    }
+}
+
+/// spreadsheet of the texts of a string table, as csv, tsv or xlsx.
+///
+/// | id   | source | translation | speaker | notes            | status       |
+/// |------|--------|-------------|---------|------------------|--------------|
+/// | 1024 | 原文   |             |         | length: 32 bytes | untranslated |
+///
+/// the first row names the columns, which are found by name: they may be reordered, and
+/// other columns are ignored. rows are matched to the items by `id`, in any order. csv and
+/// tsv files are written with a utf-8 BOM for Excel, and read back in utf-8 or utf-16 with
+/// or without a BOM, with CRLF line breaks and line breaks in quoted cells.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sheet {
+    pub rows: Vec<SheetRow>,
+}
+
+impl Sheet {
+    /// spreadsheet of the texts of the string table, waiting for translation.
+    pub fn export(data: &DataDispatcher) -> DispatcherResult<Self> {
+        let DataDispatcher::StringTable(_) = data else {
+            return Err(DispatcherError::UnsupportedTable {
+                table: data.type_name().to_string(),
+                format: "spreadsheets",
+            });
+        };
+        let rows = data
+            .text_entries("spreadsheets")?
+            .into_iter()
+            .map(|entry| SheetRow {
+                notes: entry.notes().join("; "),
+                id: entry.key,
+                source: entry.text,
+                status: Some(TranslationStatus::Untranslated),
+                ..SheetRow::default()
+            })
+            .collect();
+        Ok(Self { rows })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, format: SheetFormat) -> DispatcherResult<Self> {
+        let path = path.as_ref();
+        fs::read(path)
+            .map_err(DispatcherError::from)
+            .and_then(|bytes| Self::from_bytes(&bytes, format))
+            .map_err(|e| DispatcherError::LoadFailed {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })
+    }
+
+    pub fn from_bytes(bytes: &[u8], format: SheetFormat) -> DispatcherResult<Self> {
+        Self::from_records(read_records(bytes, format)?, format)
+    }
+
+    /// rows of the records of a spreadsheet, with their line numbers. the first non-empty
+    /// record names the columns.
+    fn from_records(
+        records: Vec<(usize, Vec<String>)>,
+        format: SheetFormat,
+    ) -> DispatcherResult<Self> {
+        let invalid = |line, reason: String| DispatcherError::InvalidTranslation {
+            format: format.name(),
+            line,
+            reason,
+        };
+        let mut records = records
+            .into_iter()
+            .filter(|(_, cells)| cells.iter().any(|cell| !cell.trim().is_empty()));
+        let Some((header_line, header)) = records.next() else {
+            return Ok(Self::default());
+        };
+
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|cell| cell.trim().eq_ignore_ascii_case(name))
+        };
+        let [id, source, translation, speaker, notes, status] = COLUMNS.map(column);
+        let (Some(id), Some(translation)) = (id, translation) else {
+            return Err(invalid(
+                header_line,
+                "the first row names no `id` or no `translation` column".to_string(),
+            ));
+        };
+
+        let mut rows = vec![];
+        let mut lines = HashMap::new();
+        for (line, cells) in records {
+            let cell = |column: Option<usize>| {
+                column
+                    .and_then(|column| cells.get(column))
+                    .cloned()
+                    .unwrap_or_default()
+            };
+            let status = match cell(status).trim() {
+                "" => None,
+                name => Some(
+                    TranslationStatus::from_name(name)
+                        .ok_or_else(|| invalid(line, format!("unknown status `{name}`")))?,
+                ),
+            };
+            let row_id = cell(Some(id)).trim().to_string();
+            if let Some(first_line) = lines
+                .insert(row_id.clone(), line)
+                .filter(|_| !row_id.is_empty())
+            {
+                return Err(invalid(
+                    line,
+                    format!("id `{row_id}` is already on line {first_line}"),
+                ));
+            }
+            rows.push(SheetRow {
+                id: row_id,
+                source: cell(source),
+                translation: cell(Some(translation)),
+                speaker: cell(speaker),
+                notes: cell(notes),
+                status,
+            });
+        }
+        Ok(Self { rows })
+    }
+
+    pub fn to_bytes(&self, format: SheetFormat) -> DispatcherResult<Vec<u8>> {
+        match format {
+            SheetFormat::Csv | SheetFormat::Tsv => self.write_delimited(format),
+            SheetFormat::Xlsx => self
+                .write_workbook()
+                .map_err(|e| DispatcherError::InvalidWorkbook(e.to_string())),
+        }
+    }
+
+    /// write the translated rows into `data`, the table read from the original file.
+    pub fn apply(&self, data: &mut DataDispatcher) -> DispatcherResult<ImportSummary> {
+        let texts = self.rows.iter().map(|row| {
+            (
+                row.id.clone(),
+                row.is_ready().then(|| row.translation.clone()),
+            )
+        });
+        data.import_texts(DataDispatcherType::StringTable, texts, "spreadsheets")
+    }
+
+    fn cells(row: &SheetRow) -> [&str; 6] {
+        [
+            &row.id,
+            &row.source,
+            &row.translation,
+            &row.speaker,
+            &row.notes,
+            row.status.map_or("", TranslationStatus::as_str),
+        ]
+    }
+
+    fn write_delimited(&self, format: SheetFormat) -> DispatcherResult<Vec<u8>> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(format.delimiter())
+            .terminator(csv::Terminator::CRLF)
+            .from_writer(UTF8_BOM.to_vec());
+        writer.write_record(COLUMNS)?;
+        for row in &self.rows {
+            writer.write_record(Self::cells(row))?;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| DispatcherError::Io(e.into_error()))
+    }
+
+    fn write_workbook(&self) -> Result<Vec<u8>, rust_xlsxwriter::XlsxError> {
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet().set_name("StringTable")?;
+        let header = Format::new().set_bold();
+        let text = Format::new().set_text_wrap();
+        for (column, name) in COLUMNS.into_iter().enumerate() {
+            worksheet.write_string_with_format(0, column as u16, name, &header)?;
+        }
+        for (column, width) in [10, 48, 48, 12, 32, 14].into_iter().enumerate() {
+            worksheet.set_column_width(column as u16, width)?;
+        }
+        worksheet.set_freeze_panes(1, 0)?;
+        for (index, row) in self.rows.iter().enumerate() {
+            for (column, cell) in Self::cells(row).into_iter().enumerate() {
+                worksheet.write_string_with_format(index as u32 + 1, column as u16, cell, &text)?;
+            }
+        }
+        workbook.save_to_buffer()
+    }
+}
+
+/// records of a spreadsheet with their line numbers, the empty records included.
+pub(crate) fn read_records(
+    bytes: &[u8],
+    format: SheetFormat,
+) -> DispatcherResult<Vec<(usize, Vec<String>)>> {
+    match format {
+        SheetFormat::Csv | SheetFormat::Tsv => read_delimited(bytes, format),
+        SheetFormat::Xlsx => read_workbook(bytes),
+    }
+}
+
+/// records of a csv or tsv file in utf-8 or utf-16, told apart by the BOM.
+///
+/// other encodings, like the windows-932 of a csv saved by a japanese Excel, are an error
+/// instead of being imported as replacement characters.
+fn read_delimited(
+    bytes: &[u8],
+    format: SheetFormat,
+) -> DispatcherResult<Vec<(usize, Vec<String>)>> {
+    let (encoding, bom_length) = Encoding::for_bom(bytes).unwrap_or((UTF_8, 0));
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let input = &bytes[bom_length..];
+    let mut text = String::with_capacity(
+        decoder
+            .max_utf8_buffer_length_without_replacement(input.len())
+            .unwrap_or_default(),
+    );
+    if let (DecoderResult::Malformed(malformed, consumed_after), read) =
+        decoder.decode_to_string_without_replacement(input, &mut text, true)
+    {
+        let offset = bom_length + read - malformed as usize - consumed_after as usize;
+        return Err(DispatcherError::InvalidTranslation {
+            format: format.name(),
+            line: text.matches('\n').count() + 1,
+            reason: format!(
+                "invalid {} at byte {offset}, save the file as utf-8 or utf-16",
+                encoding.name()
+            ),
+        });
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(format.delimiter())
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let mut records = vec![];
+    let (mut line, mut counted) = (1, 0);
+    for record in reader.records() {
+        let record = record?;
+        // the position is before the blank lines skipped by the reader, whose line count
+        // misses the CRLF ones: count the lines up to the first byte of the record.
+        let position = record
+            .position()
+            .map_or(counted, |position| position.byte() as usize);
+        let start = position
+            + text.as_bytes()[position..]
+                .iter()
+                .take_while(|&&byte| matches!(byte, b'\r' | b'\n'))
+                .count();
+        line += text.as_bytes()[counted..start]
+            .iter()
+            .filter(|&&byte| byte == b'\n')
+            .count();
+        counted = start;
+        // line breaks in quoted cells are kept as LF, like in the tables.
+        let cells = record
+            .iter()
+            .map(|cell| cell.replace("\r\n", "\n"))
+            .collect();
+        records.push((line, cells));
+    }
+    Ok(records)
+}
+
+/// records of the first worksheet of a xlsx workbook, with their row numbers.
+fn read_workbook(bytes: &[u8]) -> DispatcherResult<Vec<(usize, Vec<String>)>> {
+    let invalid = |e: calamine::XlsxError| DispatcherError::InvalidWorkbook(e.to_string());
+    let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(bytes)).map_err(invalid)?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| DispatcherError::InvalidWorkbook("no worksheet".to_string()))?
+        .map_err(invalid)?;
+    let first_row = range.start().map_or(0, |(row, _)| row as usize);
+    Ok(range
+        .rows()
+        .enumerate()
+        .map(|(index, cells)| {
+            // the row number as shown by Excel.
+            let line = first_row + index + 1;
+            (line, cells.iter().map(|cell| cell.to_string()).collect())
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet() -> Sheet {
+        Sheet {
+            rows: vec![
+                SheetRow {
+                    id: "1024".to_string(),
+                    source: "「結希」, \"quoted\"\nsecond line".to_string(),
+                    translation: "\"Yuki\",\tthe\nsister".to_string(),
+                    speaker: "結希".to_string(),
+                    notes: "length: 32 bytes".to_string(),
+                    status: Some(TranslationStatus::Reviewed),
+                },
+                SheetRow {
+                    id: "1025".to_string(),
+                    source: "お兄さん".to_string(),
+                    ..SheetRow::default()
+                },
+            ],
+        }
+    }
+
+    fn parse_error(bytes: &[u8], format: SheetFormat) -> String {
+        match Sheet::from_bytes(bytes, format) {
+            Err(error) => error.to_string(),
+            Ok(sheet) => panic!("expected an error, got {sheet:?}"),
+        }
+    }
+
+    #[test]
+    fn written_sheet_is_read_back() {
+        for format in [SheetFormat::Csv, SheetFormat::Tsv, SheetFormat::Xlsx] {
+            let bytes = sheet().to_bytes(format).unwrap();
+            assert_eq!(
+                Sheet::from_bytes(&bytes, format).unwrap(),
+                sheet(),
+                "{format:?}"
+            );
+        }
+        let csv = sheet().to_bytes(SheetFormat::Csv).unwrap();
+        assert!(csv.starts_with(UTF8_BOM));
+        assert!(csv.ends_with(b"1025,\xE3\x81\x8A\xE5\x85\x84\xE3\x81\x95\xE3\x82\x93,,,,\r\n"));
+    }
+
+    #[test]
+    fn columns_are_found_by_name() {
+        let csv = "\r\n\
+                   Translation,Notes,Comment,ID,Status\r\n\
+                   \"Yuki\r\nthe sister\",,ok,1024,Translated\r\n\
+                   ,,,,\r\n\
+                   draft text,,,1025,DRAFT\r\n\
+                   kept,,,1026\r\n";
+        let sheet = Sheet::from_bytes(csv.as_bytes(), SheetFormat::Csv).unwrap();
+        let rows: Vec<_> = sheet
+            .rows
+            .iter()
+            .map(|row| (row.id.as_str(), row.translation.as_str(), row.status))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "1024",
+                    "Yuki\nthe sister",
+                    Some(TranslationStatus::Translated)
+                ),
+                ("1025", "draft text", Some(TranslationStatus::Draft)),
+                ("1026", "kept", None),
+            ]
+        );
+        let ready: Vec<_> = sheet.rows.iter().map(SheetRow::is_ready).collect();
+        assert_eq!(ready, [true, false, true]);
+    }
+
+    #[test]
+    fn utf16_files_are_read() {
+        let tsv = "id\ttranslation\r\n1024\t結希\r\n";
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(tsv.encode_utf16().flat_map(u16::to_le_bytes));
+        let sheet = Sheet::from_bytes(&bytes, SheetFormat::Tsv).unwrap();
+        assert_eq!(sheet.rows[0].translation, "結希");
+        assert!(
+            Sheet::from_bytes(b"", SheetFormat::Csv)
+                .unwrap()
+                .rows
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn invalid_sheets_report_the_line() {
+        assert_eq!(
+            parse_error(b"\nid,source\n1,a\n", SheetFormat::Csv),
+            "invalid csv file at line 2: the first row names no `id` or no `translation` column"
+        );
+        assert_eq!(
+            parse_error(b"id\ttranslation\tstatus\n1\ta\tdone\n", SheetFormat::Tsv),
+            "invalid tsv file at line 2: unknown status `done`"
+        );
+        assert!(Sheet::from_bytes(b"not a workbook", SheetFormat::Xlsx).is_err());
+    }
+
+    #[test]
+    fn other_encodings_are_rejected() {
+        // "結希" in windows-932, as saved by a japanese Excel.
+        assert_eq!(
+            parse_error(
+                b"id,translation\r\n1024,\x8C\x8B\x8A\xF3\r\n",
+                SheetFormat::Csv
+            ),
+            "invalid csv file at line 2: invalid UTF-8 at byte 21, save the file as utf-8 or utf-16"
+        );
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(
+            "id\ttranslation\n"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        );
+        bytes.extend([0x00, 0xD8, 0x41, 0x00]);
+        assert_eq!(
+            parse_error(&bytes, SheetFormat::Tsv),
+            "invalid tsv file at line 2: invalid UTF-16LE at byte 32, save the file as utf-8 or utf-16"
+        );
+    }
+
+    #[test]
+    fn duplicate_ids_are_rejected() {
+        let csv = b"id,translation\n1024,a\n,\n,\n 1024 ,b\n";
+        assert_eq!(
+            parse_error(csv, SheetFormat::Csv),
+            "invalid csv file at line 5: id `1024` is already on line 2"
+        );
+    }
+
+    #[test]
+    fn line_numbers_count_blank_lines_and_line_breaks_in_cells() {
+        let csv = "id,translation\r\n\r\n\"a\r\nb\",x\r\n\n1,a\n";
+        let lines: Vec<_> = read_records(csv.as_bytes(), SheetFormat::Csv)
+            .unwrap()
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect();
+        assert_eq!(lines, [1, 3, 6]);
+    }
+}
@@ -0,0 +1,377 @@
+use crate::{BinaryPatch, DataDispatcherHeader, text::DecodeIssue};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// item in string table patch:
+///
+/// ```{text}
+/// 0                   1
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |   ID  |LEN|                   |
+/// +-+-+-+-+-+-+                   +
+/// |              DATA             |
+/// +                         +-+-+-+
+/// |                         | PAD |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 0-3: id, u32 little-endian;
+/// 4-5: length, u16 little-endian;
+/// 6-: data, string (length bytes, padded so the whole item is 4 bytes aligned);
+/// ```
+///
+/// ## Note
+/// the actual content of the string needs to be obtained by bitwise negation.
+/// the end of the string can be identified by the following characteristics：
+/// - string ends with a LF (\n): 0xF5 0xFF
+/// - string ends with a null (\0): 0xFF 0xFF
+///
+/// the data is split into `text`, `terminator` and `padding`: the text is followed by
+/// the terminator, then padded with nulls until the whole item (6 bytes head + data)
+/// is 4 bytes aligned. the serializer regenerates the padding. data without a terminator
+/// is kept as [StringTerminator::Missing] and reported by [crate::validate].
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, BinaryPatch)]
+#[serde(try_from = "StringTableItemDump")]
+pub struct StringTableItem {
+    #[le]
+    id: u32,
+    #[skip]
+    length: u16,
+    #[len_prefix(u16, store = length)]
+    #[encoding]
+    #[negate]
+    #[align(4)]
+    #[split(split_data, join_data)]
+    text: String,
+    #[skip]
+    terminator: StringTerminator,
+    #[skip]
+    padding: u8,
+}
+
+/// end of the text in a [StringTableItem].
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum StringTerminator {
+    /// LF followed by a null, 0xF5 0xFF once negated.
+    Lf,
+    /// two nulls, 0xFF 0xFF once negated.
+    #[default]
+    Nul,
+    /// neither, the text is followed by the padding only: written back as read, never
+    /// found in the original tables.
+    Missing,
+}
+
+impl StringTerminator {
+    fn as_str(self) -> &'static str {
+        match self {
+            StringTerminator::Lf => "\n\0",
+            StringTerminator::Nul => "\0\0",
+            StringTerminator::Missing => "",
+        }
+    }
+
+    /// bytes of the terminator, before the negation.
+    pub(crate) fn as_bytes(self) -> &'static [u8] {
+        self.as_str().as_bytes()
+    }
+}
+
+impl StringTableItem {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// length of the data as read from the binary file, recomputed when serializing.
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    /// text without the terminator and the padding.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+    }
+
+    pub fn terminator(&self) -> StringTerminator {
+        self.terminator
+    }
+
+    pub fn set_terminator(&mut self, terminator: StringTerminator) {
+        self.terminator = terminator;
+    }
+
+    /// count of padding nulls as read from the binary file, recomputed when serializing.
+    pub fn padding(&self) -> u8 {
+        self.padding
+    }
+
+    /// data without the padding, the padding is added back when serializing.
+    fn join_data(&self) -> String {
+        format!("{}{}", self.text, self.terminator.as_str())
+    }
+
+    /// split the decoded data into text, terminator and padding.
+    fn split_data(&mut self, data: &str) {
+        let text = data.trim_end_matches('\0');
+        let null_count = data.len() - text.len();
+        let (text, terminator) = match (text.strip_suffix('\n'), null_count) {
+            (Some(text), 1..) => (text, StringTerminator::Lf),
+            (None, 2..) => (text, StringTerminator::Nul),
+            _ => (text, StringTerminator::Missing),
+        };
+        let terminator_nulls = terminator.as_str().matches('\0').count();
+        self.padding = u8::try_from(null_count - terminator_nulls).unwrap_or(u8::MAX);
+        (self.text, self.terminator) = (text.to_string(), terminator);
+    }
+}
+
+/// [StringTableItem] as written in the ron dump, dumps of older versions keep the
+/// terminator and the padding in `data` instead.
+#[derive(Deserialize)]
+struct StringTableItemDump {
+    id: u32,
+    length: u16,
+    #[serde(default, deserialize_with = "present")]
+    text: Option<String>,
+    #[serde(default)]
+    terminator: StringTerminator,
+    #[serde(default)]
+    padding: u8,
+    #[serde(default, deserialize_with = "present")]
+    data: Option<String>,
+}
+
+/// optional field written without `Some(...)` in the dump.
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    String::deserialize(deserializer).map(Some)
+}
+
+impl TryFrom<StringTableItemDump> for StringTableItem {
+    type Error = String;
+
+    fn try_from(dump: StringTableItemDump) -> Result<Self, Self::Error> {
+        let mut item = Self {
+            id: dump.id,
+            length: dump.length,
+            ..Self::default()
+        };
+        match (dump.text, dump.data) {
+            (Some(text), None) => {
+                item.text = text;
+                item.terminator = dump.terminator;
+                item.padding = dump.padding;
+            }
+            (None, Some(data)) => item.split_data(&data),
+            (Some(_), Some(_)) => return Err("both `text` and `data` are given".to_string()),
+            (None, None) => return Err("missing field `text`".to_string()),
+        }
+        Ok(item)
+    }
+}
+
+/// structure of string table patch:
+///
+/// ```{text}
+/// 0                   1
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |[|S|T|R|T|B|L|]|  CNT  |  UNK  |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |   ID  |   L   |   U   |       |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+       +
+/// |                               |
+/// +              DATA             +
+/// |                               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///
+/// 0-7: header, `[STRTBL]`;
+/// 8-11: item_count, u32 little-endian;
+/// 12-15: unknown (assume as magic number), u32 little-endian;
+/// 16-: item, [StringTableItem];
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, BinaryPatch)]
+#[magic(b"[STRTBL]")]
+pub struct StringTable {
+    #[le]
+    #[count_of(items)]
+    item_count: u32,
+    #[le]
+    assume_magic_number: u32,
+    #[items]
+    items: Vec<StringTableItem>,
+}
+
+impl StringTable {
+    /// item count as read from the binary file, recomputed when serializing.
+    pub fn item_count(&self) -> u32 {
+        self.item_count
+    }
+
+    pub fn assume_magic_number(&self) -> u32 {
+        self.assume_magic_number
+    }
+
+    pub fn items(&self) -> &[StringTableItem] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut Vec<StringTableItem> {
+        &mut self.items
+    }
+
+    /// items whose data could not be fully decoded, `base_offset` is the offset of the magic header.
+    pub fn decode_issues(&self, base_offset: u64) -> Vec<DecodeIssue> {
+        let mut offset = base_offset + 16;
+        self.items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let issue =
+                    DecodeIssue::check(Self::TABLE_NAME, index, Some(item.id), offset, &item.text);
+                offset += 6 + item.length as u64;
+                issue
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeserializePatch, SerializePatch, TextEncoding};
+
+    /// string table item of negated `raw_data`.
+    fn item(id: u32, raw_data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_le_bytes().to_vec();
+        bytes.extend((raw_data.len() as u16).to_le_bytes());
+        bytes.extend(raw_data.iter().map(|byte| !byte));
+        bytes
+    }
+
+    /// "AB\n" and "結希" in windows-932.
+    fn table() -> Vec<u8> {
+        let mut bytes = b"[STRTBL]".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(item(1024, b"AB\n\0\0\0"));
+        bytes.extend(item(1025, b"\x8C\x8B\x8A\xF3\0\0"));
+        bytes
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let bytes = table();
+        let table = StringTable::from_bytes(&bytes, TextEncoding::Cp932).unwrap();
+        assert_eq!(table.item_count(), 2);
+        assert_eq!(table.assume_magic_number(), 1);
+
+        let items = table.items();
+        assert_eq!(
+            (items[0].id(), items[0].text(), items[0].terminator()),
+            (1024, "AB", StringTerminator::Lf)
+        );
+        assert_eq!((items[0].length(), items[0].padding()), (6, 2));
+        assert_eq!(
+            (items[1].id(), items[1].text(), items[1].terminator()),
+            (1025, "結希", StringTerminator::Nul)
+        );
+        assert_eq!((items[1].length(), items[1].padding()), (6, 0));
+
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), bytes);
+        assert!(table.decode_issues(0).is_empty());
+    }
+
+    #[test]
+    fn edited_text_is_padded_again() {
+        let mut table = StringTable::from_bytes(&table(), TextEncoding::Cp932).unwrap();
+        table.items_mut()[0].set_text("ABC");
+        table.items_mut()[1].set_terminator(StringTerminator::Lf);
+        table.items_mut().push(StringTableItem {
+            id: 1026,
+            ..StringTableItem::default()
+        });
+
+        let mut expected = b"[STRTBL]".to_vec();
+        expected.extend(3u32.to_le_bytes());
+        expected.extend(1u32.to_le_bytes());
+        expected.extend(item(1024, b"ABC\n\0\0"));
+        expected.extend(item(1025, b"\x8C\x8B\x8A\xF3\n\0"));
+        expected.extend(item(1026, b"\0\0"));
+        let bytes = table.to_bytes(TextEncoding::Cp932).unwrap();
+        assert_eq!(bytes, expected);
+
+        let read = StringTable::from_bytes(&bytes, TextEncoding::Cp932).unwrap();
+        assert_eq!(read.items()[0].text(), "ABC");
+        assert_eq!(read.items()[1].terminator(), StringTerminator::Lf);
+        assert_eq!(read.items()[2].text(), "");
+    }
+
+    #[test]
+    fn odd_length_text_ends_with_two_nulls() {
+        let mut table = StringTable::from_bytes(&table(), TextEncoding::Cp932).unwrap();
+        table.items_mut()[1].set_text("結希!");
+        table.items_mut()[0].set_text("A");
+        table.items_mut()[0].set_terminator(StringTerminator::Nul);
+
+        let mut expected = b"[STRTBL]".to_vec();
+        expected.extend(2u32.to_le_bytes());
+        expected.extend(1u32.to_le_bytes());
+        expected.extend(item(1024, b"A\0\0\0\0\0"));
+        expected.extend(item(1025, b"\x8C\x8B\x8A\xF3!\0\0\0\0\0"));
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), expected);
+    }
+
+    #[test]
+    fn missing_terminator_is_kept() {
+        for raw_data in [&b"AB"[..], b"A\0", b"ABC\n\0\0"] {
+            let mut bytes = b"[STRTBL]".to_vec();
+            bytes.extend(1u32.to_le_bytes());
+            bytes.extend(1u32.to_le_bytes());
+            bytes.extend(item(1, raw_data));
+
+            let table = StringTable::from_bytes(&bytes, TextEncoding::Cp932).unwrap();
+            let expected = match raw_data.ends_with(b"\0\0") {
+                true => StringTerminator::Lf,
+                false => StringTerminator::Missing,
+            };
+            assert_eq!(table.items()[0].terminator(), expected);
+            assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn undecodable_bytes_are_reported_and_kept() {
+        let mut bytes = b"[STRTBL]".to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(item(7, b"A\xA0\0\0\0\0"));
+
+        let table = StringTable::from_bytes(&bytes, TextEncoding::Cp932).unwrap();
+        let issues = table.decode_issues(0x20);
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].id, issues[0].offset), (Some(7), 0x30));
+        assert_eq!(issues[0].raw_bytes, [0xA0]);
+        assert_eq!(table.to_bytes(TextEncoding::Cp932).unwrap(), bytes);
+    }
+
+    #[test]
+    fn truncated_table_is_an_error() {
+        let bytes = table();
+        assert!(StringTable::from_bytes(&bytes[..bytes.len() - 1], TextEncoding::Cp932).is_err());
+        assert!(StringTable::from_bytes(b"[MESNAM]", TextEncoding::Cp932).is_err());
+    }
+
+    #[test]
+    fn older_dumps_keep_the_terminator_in_the_data() {
+        let item: StringTableItem =
+            ron::from_str(r#"(id: 5, length: 6, data: "AB\n\u{0}\u{0}\u{0}")"#).unwrap();
+        assert_eq!(
+            (item.text(), item.terminator(), item.padding()),
+            ("AB", StringTerminator::Lf, 2)
+        );
+        assert!(ron::from_str::<StringTableItem>(r#"(id: 5, length: 6)"#).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+use crate::{DispatcherError, DispatcherResult};
+use clap::ValueEnum;
+use encoding_rs::{DecoderResult, Encoding, GB18030, GBK, SHIFT_JIS, UTF_8};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// undecodable bytes are kept in the text as `U+10FF00 + byte`.
+///
+/// plane 16 private use characters can't be encoded in the legacy encodings, so they never
+/// collide with real text and are turned back into the original byte when encoding.
+const RAW_BYTE_BASE: u32 = 0x10FF00;
+
+/// wrap an undecodable byte into its escape character.
+pub fn escape_raw_byte(byte: u8) -> char {
+    char::from_u32(RAW_BYTE_BASE + byte as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// original byte of an escape character produced by [escape_raw_byte].
+pub fn unescape_raw_byte(ch: char) -> Option<u8> {
+    (ch as u32)
+        .checked_sub(RAW_BYTE_BASE)
+        .and_then(|byte| u8::try_from(byte).ok())
+}
+
+/// text encoding of the table items.
+///
+/// `encoding_rs` implements the WHATWG flavour of Shift_JIS, which is actually windows-932
+/// (CP932): [TextEncoding::Cp932] uses it, while [TextEncoding::ShiftJis] sticks to
+/// JIS X 0208 and treats the NEC and IBM extensions as undecodable. windows-932 maps some
+/// characters twice, like the NEC selected IBM extensions and the IBM ones, or ≒ in NEC
+/// row 13 and in JIS X 0208: the bytes the encoder would not write back are undecodable
+/// for [TextEncoding::Cp932] too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextEncoding {
+    /// plain Shift-JIS (JIS X 0208), without the windows-932 extensions.
+    #[value(name = "shift-jis", alias = "sjis")]
+    ShiftJis,
+    /// windows-932, the Shift-JIS used by the original game.
+    #[default]
+    #[value(name = "cp932", alias = "windows-932")]
+    Cp932,
+    #[value(name = "gbk", alias = "cp936")]
+    Gbk,
+    #[value(name = "utf-8", alias = "utf8")]
+    Utf8,
+}
+
+/// characters decoded differently by the two Shift-JIS flavours, as `(shift-jis, cp932)`,
+/// e.g. 0x8160 is WAVE DASH in JIS X 0208 but FULLWIDTH TILDE in windows-932.
+const SHIFT_JIS_CP932_PAIRS: [(char, char); 6] = [
+    ('\u{301C}', '\u{FF5E}'),
+    ('\u{2016}', '\u{2225}'),
+    ('\u{2212}', '\u{FF0D}'),
+    ('\u{00A2}', '\u{FFE0}'),
+    ('\u{00A3}', '\u{FFE1}'),
+    ('\u{00AC}', '\u{FFE2}'),
+];
+
+impl TextEncoding {
+    /// name used in error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            TextEncoding::ShiftJis => "Shift-JIS",
+            TextEncoding::Cp932 => "windows-932",
+            TextEncoding::Gbk => "GBK",
+            TextEncoding::Utf8 => "UTF-8",
+        }
+    }
+
+    fn encoding_rs(self) -> &'static Encoding {
+        match self {
+            TextEncoding::ShiftJis | TextEncoding::Cp932 => SHIFT_JIS,
+            TextEncoding::Gbk => GBK,
+            TextEncoding::Utf8 => UTF_8,
+        }
+    }
+
+    /// decode the raw data without losing anything.
+    ///
+    /// each undecodable byte is kept in the text as an escape character, see [escape_raw_byte].
+    pub fn decode(self, raw_data: &[u8]) -> String {
+        match self {
+            TextEncoding::ShiftJis => decode_shift_jis(raw_data),
+            TextEncoding::Cp932 => decode_escaping(raw_data, is_cp932_duplicate),
+            _ => decode_lossless(self.encoding_rs(), raw_data),
+        }
+    }
+
+    /// encode the text, escape characters are written back as the original bytes.
+    ///
+    /// unmappable characters are treated as errors instead of being replaced by html
+    /// numeric references.
+    pub fn encode(self, text: &str) -> DispatcherResult<Vec<u8>> {
+        let mut raw_data = Vec::with_capacity(text.len());
+        let mut run_start = 0;
+        for (pos, ch) in text.char_indices() {
+            if let Some(byte) = unescape_raw_byte(ch) {
+                self.encode_run(&text[run_start..pos], &mut raw_data)?;
+                raw_data.push(byte);
+                run_start = pos + ch.len_utf8();
+            }
+        }
+        self.encode_run(&text[run_start..], &mut raw_data)?;
+        Ok(raw_data)
+    }
+
+    fn encode_run(self, run: &str, raw_data: &mut Vec<u8>) -> DispatcherResult<()> {
+        if run.is_empty() {
+            return Ok(());
+        }
+        let encoding_error = || DispatcherError::EncodingError {
+            text: run.to_string(),
+            encoding: self.name(),
+        };
+
+        let mapped;
+        let run = match self {
+            TextEncoding::ShiftJis => {
+                // windows-932 only characters are unmappable, the JIS X 0208 ones share
+                // their bytes.
+                mapped = run
+                    .chars()
+                    .map(
+                        |ch| match SHIFT_JIS_CP932_PAIRS.iter().find(|pair| pair.0 == ch) {
+                            Some(&(_, cp932)) => Ok(cp932),
+                            None if SHIFT_JIS_CP932_PAIRS.iter().any(|pair| pair.1 == ch) => {
+                                Err(encoding_error())
+                            }
+                            None => Ok(ch),
+                        },
+                    )
+                    .collect::<DispatcherResult<String>>()?;
+                mapped.as_str()
+            }
+            _ => run,
+        };
+
+        let (bytes, _, had_errors) = self.encoding_rs().encode(run);
+        if had_errors {
+            return Err(encoding_error());
+        }
+        if self == TextEncoding::ShiftJis
+            && shift_jis_chars(&bytes).any(|(_, char_bytes)| is_cp932_extension(char_bytes[0]))
+        {
+            return Err(encoding_error());
+        }
+        raw_data.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+/// decode with `encoding`, each malformed sequence is escaped byte by byte.
+fn decode_lossless(encoding: &'static Encoding, raw_data: &[u8]) -> String {
+    let mut text = String::with_capacity(raw_data.len() * 2);
+
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut decoded = String::new();
+    let mut pos = 0;
+    loop {
+        let input = &raw_data[pos..];
+        decoded.clear();
+        if let Some(needed) = decoder.max_utf8_buffer_length_without_replacement(input.len()) {
+            decoded.reserve(needed);
+        }
+        let (result, read) =
+            decoder.decode_to_string_without_replacement(input, &mut decoded, true);
+        push_decoded(encoding, &decoded, &mut text);
+        match result {
+            DecoderResult::InputEmpty => break,
+            DecoderResult::OutputFull => pos += read,
+            DecoderResult::Malformed(malformed, consumed_after) => {
+                // escape the malformed sequence, and re-push the bytes consumed after it
+                // to a fresh decoder.
+                let end = pos + read - consumed_after as usize;
+                let start = end - malformed as usize;
+                text.extend(
+                    raw_data[start..end]
+                        .iter()
+                        .map(|&byte| escape_raw_byte(byte)),
+                );
+                pos = end;
+                decoder = encoding.new_decoder_without_bom_handling();
+            }
+        }
+    }
+
+    text
+}
+
+/// push the decoded characters, escaping the ones that wouldn't be written back as the
+/// same bytes: plane 16 characters look like escape characters, and the GBK decoder also
+/// accepts the four byte GB18030 sequences the GBK encoder can't produce.
+fn push_decoded(encoding: &'static Encoding, decoded: &str, text: &mut String) {
+    for ch in decoded.chars() {
+        let mut buffer = [0; 4];
+        let ch_str = ch.encode_utf8(&mut buffer);
+        let reversible = unescape_raw_byte(ch).is_none()
+            && (encoding != GBK || ch.is_ascii() || !GBK.encode(ch_str).2);
+        if reversible {
+            text.push(ch);
+        } else {
+            let source = if encoding == GBK { GB18030 } else { encoding };
+            let (bytes, _, _) = source.encode(ch_str);
+            text.extend(bytes.iter().map(|&byte| escape_raw_byte(byte)));
+        }
+    }
+}
+
+/// decode as windows-932, escaping the characters whose bytes match `escape`.
+fn decode_escaping(raw_data: &[u8], escape: impl Fn(&[u8]) -> bool) -> String {
+    let mut text = String::with_capacity(raw_data.len() * 2);
+    let mut run_start = 0;
+    for (pos, bytes) in shift_jis_chars(raw_data) {
+        if escape(bytes) {
+            text.push_str(&decode_lossless(SHIFT_JIS, &raw_data[run_start..pos]));
+            text.extend(bytes.iter().map(|&byte| escape_raw_byte(byte)));
+            run_start = pos + bytes.len();
+        }
+    }
+    text.push_str(&decode_lossless(SHIFT_JIS, &raw_data[run_start..]));
+    text
+}
+
+/// decode as windows-932, then escape the extension characters and map the rest back to
+/// JIS X 0208.
+fn decode_shift_jis(raw_data: &[u8]) -> String {
+    decode_escaping(raw_data, |bytes| is_cp932_extension(bytes[0]))
+        .chars()
+        .map(
+            |ch| match SHIFT_JIS_CP932_PAIRS.iter().find(|pair| pair.1 == ch) {
+                Some(&(shift_jis, _)) => shift_jis,
+                None => ch,
+            },
+        )
+        .collect()
+}
+
+/// split Shift-JIS bytes into characters, as `(offset, bytes)`.
+fn shift_jis_chars(raw_data: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let lead = *raw_data.get(pos)?;
+        let length = match lead {
+            0x81..=0x9F | 0xE0..=0xFC => 2.min(raw_data.len() - pos),
+            _ => 1,
+        };
+        let start = pos;
+        pos += length;
+        Some((start, &raw_data[start..pos]))
+    })
+}
+
+/// double byte windows-932 character the encoder writes as other bytes, e.g. 0xED40 is
+/// written as 0xFA5C. malformed sequences are left to the decoder.
+fn is_cp932_duplicate(bytes: &[u8]) -> bool {
+    bytes.len() == 2
+        && SHIFT_JIS
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .is_some_and(|ch| SHIFT_JIS.encode(&ch).0 != bytes)
+}
+
+/// lead bytes of the windows-932 extensions: NEC row 13, NEC selected IBM extensions,
+/// user defined area and IBM extensions.
+fn is_cp932_extension(lead: u8) -> bool {
+    matches!(lead, 0x87 | 0xED | 0xEE | 0xF0..=0xFC)
+}
+
+/// item whose data could not be fully decoded, reported by `decode_issues` of the tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeIssue {
+    pub table: String,
+    pub index: usize,
+    /// id of the item, only string table items have one.
+    pub id: Option<u32>,
+    /// offset of the item in the input.
+    pub offset: u64,
+    /// undecodable bytes, kept in the data as escape characters.
+    pub raw_bytes: Vec<u8>,
+}
+
+impl DecodeIssue {
+    pub(crate) fn check(
+        table: &str,
+        index: usize,
+        id: Option<u32>,
+        offset: u64,
+        data: &str,
+    ) -> Option<Self> {
+        let raw_bytes: Vec<u8> = data.chars().filter_map(unescape_raw_byte).collect();
+        (!raw_bytes.is_empty()).then(|| Self {
+            table: table.to_string(),
+            index,
+            id,
+            offset,
+            raw_bytes,
+        })
+    }
+}
+
+impl fmt::Display for DecodeIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` item {}", self.table, self.index)?;
+        if let Some(id) = self.id {
+            write!(f, " (id {id})")?;
+        }
+        write!(f, " at offset {:#x} has undecodable bytes", self.offset)?;
+        for byte in &self.raw_bytes {
+            write!(f, " {byte:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// decode `raw_data` to `text` and encode it back to the same bytes.
+    fn assert_round_trip(encoding: TextEncoding, raw_data: &[u8], text: &str) {
+        assert_eq!(encoding.decode(raw_data), text, "{}", encoding.name());
+        assert_eq!(
+            encoding.encode(text).unwrap(),
+            raw_data,
+            "{}",
+            encoding.name()
+        );
+    }
+
+    fn escaped(bytes: &[u8]) -> String {
+        bytes.iter().map(|&byte| escape_raw_byte(byte)).collect()
+    }
+
+    #[test]
+    fn raw_byte_escapes_round_trip() {
+        for byte in 0..=u8::MAX {
+            assert_eq!(unescape_raw_byte(escape_raw_byte(byte)), Some(byte));
+        }
+        assert_eq!(unescape_raw_byte('A'), None);
+        assert_eq!(unescape_raw_byte('\u{10FEFF}'), None);
+    }
+
+    #[test]
+    fn cp932_keeps_the_windows_extensions() {
+        assert_round_trip(TextEncoding::Cp932, b"\x8C\x8B\x8A\xF3", "結希");
+        assert_round_trip(TextEncoding::Cp932, b"\x81\x60\x87\x40", "\u{FF5E}①");
+        assert_round_trip(
+            TextEncoding::Cp932,
+            b"A\xA0\x81",
+            &format!("A{}", escaped(b"\xA0\x81")),
+        );
+    }
+
+    #[test]
+    fn cp932_escapes_the_duplicate_characters() {
+        // NEC selected IBM extension, NEC row 13 and IBM extension duplicates.
+        for bytes in [b"\xED\x40", b"\x87\x90", b"\xEE\xEF", b"\xFA\x4A"] {
+            assert_round_trip(TextEncoding::Cp932, bytes, &escaped(bytes));
+            let issue = DecodeIssue::check(
+                "StringTable",
+                0,
+                None,
+                0,
+                &TextEncoding::Cp932.decode(bytes),
+            );
+            assert_eq!(issue.unwrap().raw_bytes, bytes);
+        }
+        // the characters themselves are written as the bytes the encoder prefers.
+        assert_round_trip(TextEncoding::Cp932, b"\xFA\x5C\x81\xE0\xFA\x40", "纊≒ⅰ");
+    }
+
+    #[test]
+    fn shift_jis_escapes_the_windows_extensions() {
+        assert_round_trip(TextEncoding::ShiftJis, b"\x81\x60", "\u{301C}");
+        assert_round_trip(
+            TextEncoding::ShiftJis,
+            b"\x8C\x8B\x87\x40",
+            &format!("結{}", escaped(b"\x87\x40")),
+        );
+        assert!(TextEncoding::ShiftJis.encode("①").is_err());
+        assert!(TextEncoding::ShiftJis.encode("\u{FF5E}").is_err());
+    }
+
+    #[test]
+    fn gbk_escapes_the_gb18030_sequences() {
+        assert_round_trip(TextEncoding::Gbk, b"\xD6\xD0\xCE\xC4", "中文");
+        assert_round_trip(
+            TextEncoding::Gbk,
+            b"\x81\x30\x81\x30A",
+            &format!("{}A", escaped(b"\x81\x30\x81\x30")),
+        );
+    }
+
+    #[test]
+    fn utf8_escapes_invalid_and_plane_16_sequences() {
+        assert_round_trip(TextEncoding::Utf8, "結希\n".as_bytes(), "結希\n");
+        assert_round_trip(
+            TextEncoding::Utf8,
+            b"\xFFa\xC3",
+            &format!("{}a{}", escaped(b"\xFF"), escaped(b"\xC3")),
+        );
+        let plane_16 = "\u{10FF41}".as_bytes();
+        assert_round_trip(TextEncoding::Utf8, plane_16, &escaped(plane_16));
+    }
+
+    #[test]
+    fn unmappable_characters_are_an_error() {
+        for encoding in [
+            TextEncoding::ShiftJis,
+            TextEncoding::Cp932,
+            TextEncoding::Gbk,
+        ] {
+            match encoding.encode("A😀") {
+                Err(DispatcherError::EncodingError {
+                    text,
+                    encoding: name,
+                }) => {
+                    assert_eq!((text.as_str(), name), ("A😀", encoding.name()))
+                }
+                other => panic!("expected an encoding error, got {other:?}"),
+            }
+        }
+        assert_eq!(TextEncoding::Utf8.encode("😀").unwrap(), "😀".as_bytes());
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{
+    DataDispatcher, DataDispatcherType, DispatcherError, DispatcherResult, StringTerminator,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+/// text of a table item, as exchanged with the translation formats.
+///
+/// only the known tables have translatable texts, string table items are keyed by their
+/// `id` and the other items by their index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEntry {
+    pub key: String,
+    /// text without the terminator and the padding.
+    pub text: String,
+    /// length of the data as read from the binary file.
+    pub length: u16,
+    /// terminator of string table items, hidden from the text.
+    pub terminator: Option<StringTerminator>,
+}
+
+impl TextEntry {
+    /// notes for the translators: the length budget and the terminator of the original.
+    pub fn notes(&self) -> Vec<String> {
+        let mut notes = vec![format!("length: {} bytes", self.length)];
+        if let Some(terminator) = self.terminator {
+            let terminator = match terminator {
+                StringTerminator::Lf => "line feed and null",
+                StringTerminator::Nul => "null",
+                StringTerminator::Missing => "none",
+            };
+            notes[0].push_str(", terminator and padding included");
+            notes.push(format!("terminator: {terminator}"));
+        }
+        notes
+    }
+}
+
+/// review status of a translation, kept by the translators next to the text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationStatus {
+    #[default]
+    Untranslated,
+    /// work in progress, not imported yet.
+    Draft,
+    Translated,
+    Reviewed,
+}
+
+impl TranslationStatus {
+    pub const ALL: [TranslationStatus; 4] = [
+        TranslationStatus::Untranslated,
+        TranslationStatus::Draft,
+        TranslationStatus::Translated,
+        TranslationStatus::Reviewed,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TranslationStatus::Untranslated => "untranslated",
+            TranslationStatus::Draft => "draft",
+            TranslationStatus::Translated => "translated",
+            TranslationStatus::Reviewed => "reviewed",
+        }
+    }
+
+    /// status named `status`, ignoring the case.
+    pub fn from_name(status: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|known| known.as_str().eq_ignore_ascii_case(status.trim()))
+    }
+
+    /// the translation is written into the table.
+    pub fn is_ready(self) -> bool {
+        matches!(
+            self,
+            TranslationStatus::Translated | TranslationStatus::Reviewed
+        )
+    }
+}
+
+impl fmt::Display for TranslationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// outcome of importing translations into a table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub translated: usize,
+    /// entries left out, empty or not ready, the item keeps its text.
+    pub untranslated: usize,
+    /// keys matching no item of the table.
+    pub unknown_keys: Vec<String>,
+}
+
+impl DataDispatcher {
+    /// texts of the items, for exchanging them as `format`.
+    pub fn text_entries(&self, format: &'static str) -> DispatcherResult<Vec<TextEntry>> {
+        Ok(match self {
+            DataDispatcher::StringTable(string_table) => string_table
+                .items()
+                .iter()
+                .map(|item| TextEntry {
+                    key: item.id().to_string(),
+                    text: item.text().to_string(),
+                    length: item.length(),
+                    terminator: Some(item.terminator()),
+                })
+                .collect(),
+            DataDispatcher::NameTable(name_table) => name_table
+                .items()
+                .iter()
+                .enumerate()
+                .map(|(index, item)| TextEntry {
+                    key: index.to_string(),
+                    text: item.data().to_string(),
+                    length: item.length(),
+                    terminator: None,
+                })
+                .collect(),
+            DataDispatcher::FileNameTable(file_name_table) => file_name_table
+                .items()
+                .iter()
+                .enumerate()
+                .map(|(index, item)| TextEntry {
+                    key: index.to_string(),
+                    text: item.data().to_string(),
+                    length: item.length(),
+                    terminator: None,
+                })
+                .collect(),
+            DataDispatcher::Schema(_) | DataDispatcher::RawTable(_) => {
+                return Err(self.unsupported(format));
+            }
+        })
+    }
+
+    /// write the translations of a `table` exchanged as `format` into this table, read from
+    /// the original file. entries without a translation are counted as untranslated.
+    pub fn import_texts(
+        &mut self,
+        table: DataDispatcherType,
+        texts: impl IntoIterator<Item = (String, Option<String>)>,
+        format: &'static str,
+    ) -> DispatcherResult<ImportSummary> {
+        if self.patch_type() != Some(table) {
+            return Err(DispatcherError::TableTypeMismatch {
+                expected: table.table_name(),
+                found: self.type_name().to_string(),
+            });
+        }
+
+        let mut untranslated = 0;
+        let translated: Vec<_> = texts
+            .into_iter()
+            .filter_map(|(key, text)| {
+                untranslated += usize::from(text.is_none());
+                text.map(|text| (key, text))
+            })
+            .collect();
+        let count = translated.len();
+        let unknown_keys = self.set_texts(translated, format)?;
+        Ok(ImportSummary {
+            translated: count - unknown_keys.len(),
+            untranslated,
+            unknown_keys,
+        })
+    }
+
+    /// replace the texts of the items by key, exchanged as `format`. return the keys
+    /// matching no item.
+    pub fn set_texts(
+        &mut self,
+        texts: impl IntoIterator<Item = (String, String)>,
+        format: &'static str,
+    ) -> DispatcherResult<Vec<String>> {
+        let indexes: HashMap<String, usize> = self
+            .text_entries(format)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| (entry.key, index))
+            .collect();
+
+        let mut unknown_keys = vec![];
+        for (key, text) in texts {
+            match indexes.get(&key) {
+                Some(&index) => self.set_text(index, text),
+                None => unknown_keys.push(key),
+            }
+        }
+        Ok(unknown_keys)
+    }
+
+    fn set_text(&mut self, index: usize, text: String) {
+        match self {
+            DataDispatcher::StringTable(string_table) => {
+                string_table.items_mut()[index].set_text(text)
+            }
+            DataDispatcher::NameTable(name_table) => name_table.items_mut()[index].set_data(text),
+            DataDispatcher::FileNameTable(file_name_table) => {
+                file_name_table.items_mut()[index].set_data(text)
+            }
+            DataDispatcher::Schema(_) | DataDispatcher::RawTable(_) => {}
+        }
+    }
+
+    fn unsupported(&self, format: &'static str) -> DispatcherError {
+        DispatcherError::UnsupportedTable {
+            table: self.type_name().to_string(),
+            format,
+        }
+    }
+}
@@ -0,0 +1,630 @@
+use crate::{
+    DataDispatcherType, FieldKind, FieldSchema, StringTerminator, TableSchema, scan::read_int,
+};
+use serde::Serialize;
+use std::fmt;
+
+const MAGIC_LENGTH: usize = 8;
+
+/// trailing zero or 0xFF bytes up to this size are taken as alignment padding.
+const MAX_ALIGNMENT_PADDING: usize = 16;
+
+/// how serious a [Finding] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// unusual, but the table can still be dumped and patched.
+    Warning,
+    /// the table is broken, or would not be written back the same.
+    Error,
+}
+
+/// structural check a [Finding] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    /// the header or the items run past the end of the data.
+    Truncated,
+    /// the item count doesn't match the items present.
+    ItemCount,
+    /// bytes after the last item that are neither alignment padding nor another table.
+    TrailingBytes,
+    /// padding bytes of an item.
+    Padding,
+    /// terminator at the end of the text of an item.
+    Terminator,
+    /// header field with an unusual value, like `assume_magic_number`.
+    HeaderValue,
+}
+
+/// problem found by [validate] in the structure of a table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub check: Check,
+    pub table: &'static str,
+    /// offset of the magic header of the table.
+    pub table_offset: u64,
+    /// offset of the problem, both in the buffer given to [validate].
+    pub offset: u64,
+    /// item the finding is about, none for the header and the trailing bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+/// header fields and item layout of a known table, from its built-in [TableSchema].
+struct TableLayout {
+    table: &'static str,
+    /// header fields, with their offset from the end of the magic header.
+    header: Vec<(usize, FieldSchema)>,
+    /// bytes between the magic header and the first item.
+    header_length: usize,
+    item: Vec<FieldSchema>,
+    /// the text of the items ends with a [StringTerminator], then padding.
+    terminated: bool,
+    /// items are padded to this many bytes, 1 for none.
+    align: usize,
+    /// byte of the nulls as stored, 0xFF when the text is negated.
+    null: u8,
+}
+
+impl TableLayout {
+    fn of(patch_type: DataDispatcherType) -> Self {
+        let schema = TableSchema::builtin()
+            .into_iter()
+            .find(|schema| schema.name == patch_type.table_name())
+            .expect("known tables have a built-in schema");
+        let mut header = vec![];
+        let mut header_length = 0;
+        for field in schema.header {
+            let size = field.kind.size().unwrap_or_default() as usize;
+            header.push((header_length, field));
+            header_length += size;
+        }
+        let text = schema
+            .item
+            .iter()
+            .find(|field| field.kind == FieldKind::Text);
+        Self {
+            table: patch_type.table_name(),
+            header,
+            header_length,
+            terminated: text.is_some_and(|text| text.null_terminated),
+            align: text.map_or(1, |text| text.align.max(1) as usize),
+            null: match text.is_some_and(|text| text.negate) {
+                true => 0xFF,
+                false => 0x00,
+            },
+            item: schema.item,
+        }
+    }
+
+    /// `(text start, text end, item end)` of the item at `pos`, none if it runs past `end`.
+    fn item_at(&self, buffer: &[u8], pos: usize, end: usize) -> Option<(usize, usize, usize)> {
+        let mut field_pos = pos;
+        let mut text = (pos, pos);
+        for field in &self.item {
+            match field.kind.size() {
+                Some(size) => field_pos += size as usize,
+                None => {
+                    let length = read_int(buffer, field_pos, field.prefix)? as usize;
+                    let data_start = field_pos + field.prefix.size().unwrap_or_default() as usize;
+                    field_pos = data_start + length;
+                    text = (data_start, field_pos);
+                }
+            }
+        }
+        (field_pos <= end).then_some((text.0, text.1, field_pos))
+    }
+
+    /// terminator as stored.
+    fn terminator(&self, terminator: StringTerminator) -> Vec<u8> {
+        let negate = self.null == 0xFF;
+        terminator
+            .as_bytes()
+            .iter()
+            .map(|&byte| if negate { !byte } else { byte })
+            .collect()
+    }
+
+    /// offset of the terminator in the text data: after the last byte that is not a null
+    /// or padding, or at the LF before the null.
+    fn terminator_offset(&self, data: &[u8]) -> usize {
+        let text_length = data
+            .iter()
+            .rposition(|&byte| byte != 0x00 && byte != 0xFF)
+            .map_or(0, |last| last + 1);
+        let lf = self.terminator(StringTerminator::Lf);
+        text_length
+            .checked_sub(1)
+            .filter(|&last| data[last..].starts_with(&lf))
+            .unwrap_or(text_length)
+    }
+
+    /// length of the terminator the text data ends with, none if it has no documented one.
+    fn terminator_length(&self, ending: &[u8]) -> Option<usize> {
+        [StringTerminator::Lf, StringTerminator::Nul]
+            .into_iter()
+            .map(|terminator| self.terminator(terminator))
+            .find(|terminator| ending.starts_with(terminator))
+            .map(|terminator| terminator.len())
+    }
+
+    /// text data of an item: text, documented terminator and padding.
+    fn is_terminated(&self, item_length: usize, data: &[u8]) -> bool {
+        let ending = &data[self.terminator_offset(data)..];
+        item_length % self.align == 0
+            && self
+                .terminator_length(ending)
+                .is_some_and(|length| ending[length..].iter().all(|&byte| byte == self.null))
+    }
+}
+
+/// check the structure of every known table of `buffer`, beyond what reading them checks.
+///
+/// tables are found by their magic header and walked without decoding their texts, so that
+/// broken tables are reported instead of failing to parse.
+pub fn validate(buffer: &[u8]) -> Vec<Finding> {
+    let starts: Vec<(usize, DataDispatcherType)> = buffer
+        .windows(MAGIC_LENGTH)
+        .enumerate()
+        .filter_map(|(offset, window)| {
+            DataDispatcherType::ALL
+                .into_iter()
+                .find(|patch_type| patch_type.magic_header() == window)
+                .map(|patch_type| (offset, patch_type))
+        })
+        .collect();
+
+    let mut findings = vec![];
+    let mut table_end = 0;
+    for &(offset, patch_type) in &starts {
+        if offset < table_end {
+            continue;
+        }
+        // the table may run up to the next table, or to the end of the buffer.
+        let limit = starts
+            .iter()
+            .map(|&(start, _)| start)
+            .find(|&start| start > offset)
+            .unwrap_or(buffer.len());
+        let mut validator = Validator {
+            layout: TableLayout::of(patch_type),
+            buffer,
+            table_offset: offset,
+            findings: &mut findings,
+        };
+        table_end = validator.run(limit);
+    }
+    findings
+}
+
+struct Validator<'a> {
+    layout: TableLayout,
+    buffer: &'a [u8],
+    table_offset: usize,
+    findings: &'a mut Vec<Finding>,
+}
+
+impl Validator<'_> {
+    fn report(
+        &mut self,
+        severity: Severity,
+        check: Check,
+        offset: usize,
+        index: Option<usize>,
+        message: String,
+    ) {
+        self.findings.push(Finding {
+            severity,
+            check,
+            table: self.layout.table,
+            table_offset: self.table_offset as u64,
+            offset: offset as u64,
+            index,
+            message,
+        });
+    }
+
+    /// check the table, `limit` is where the next table starts. return the end of the table.
+    fn run(&mut self, limit: usize) -> usize {
+        let header_pos = self.table_offset + MAGIC_LENGTH;
+        let items_pos = header_pos + self.layout.header_length;
+        if items_pos > self.buffer.len() {
+            self.report(
+                Severity::Error,
+                Check::Truncated,
+                header_pos,
+                None,
+                "the header runs past the end of the data".to_string(),
+            );
+            return self.buffer.len();
+        }
+
+        let mut count = 0;
+        let mut unusual = vec![];
+        for (offset, field) in &self.layout.header {
+            let value = read_int(self.buffer, header_pos + offset, field.kind).unwrap_or_default();
+            if field.count {
+                count = value;
+            }
+            if let Some(usual) = field.usual.filter(|&usual| usual != value) {
+                unusual.push((
+                    header_pos + offset,
+                    format!("`{}` is {value:#x}, usually {usual:#x}", field.name),
+                ));
+            }
+        }
+        for (offset, message) in unusual {
+            self.report(Severity::Warning, Check::HeaderValue, offset, None, message);
+        }
+
+        let mut pos = items_pos;
+        for index in 0..count as usize {
+            let Some((data_start, data_end, item_end)) =
+                self.layout.item_at(self.buffer, pos, limit)
+            else {
+                self.report(
+                    Severity::Error,
+                    Check::ItemCount,
+                    pos,
+                    Some(index),
+                    format!("`item_count` is {count} but the data ends after {index} items"),
+                );
+                return limit;
+            };
+            if self.layout.terminated {
+                self.check_terminated(index, pos, data_start, data_end, item_end);
+            }
+            pos = item_end;
+        }
+
+        self.check_trailing(count, pos, limit);
+        pos
+    }
+
+    /// the text ends with a LF and a null or with two nulls, 0xF5 0xFF or 0xFF 0xFF once
+    /// negated, and padding follows up to the alignment.
+    fn check_terminated(
+        &mut self,
+        index: usize,
+        pos: usize,
+        data_start: usize,
+        data_end: usize,
+        item_end: usize,
+    ) {
+        let (align, null) = (self.layout.align, self.layout.null);
+        if (item_end - pos) % align != 0 {
+            self.report(
+                Severity::Error,
+                Check::Padding,
+                pos,
+                Some(index),
+                format!(
+                    "the item is {} bytes, not padded to {align} bytes",
+                    item_end - pos
+                ),
+            );
+        }
+
+        let data = &self.buffer[data_start..data_end];
+        let terminator_start = self.layout.terminator_offset(data);
+        let ending = &data[terminator_start..];
+        let Some(terminator_length) = self.layout.terminator_length(ending) else {
+            let expected = [StringTerminator::Lf, StringTerminator::Nul]
+                .map(|terminator| hex_bytes(&self.layout.terminator(terminator)))
+                .join(" or ");
+            let message = match ending {
+                [] => "the text has no terminator".to_string(),
+                ending => format!(
+                    "the text ends with {} instead of {expected}",
+                    hex_bytes(&ending[..ending.len().min(2)])
+                ),
+            };
+            self.report(
+                Severity::Error,
+                Check::Terminator,
+                data_start + terminator_start,
+                Some(index),
+                message,
+            );
+            return;
+        };
+
+        let padding_start = terminator_start + terminator_length;
+        let padding = &data[padding_start..];
+        if let Some(bad) = padding.iter().position(|&byte| byte != null) {
+            self.report(
+                Severity::Warning,
+                Check::Padding,
+                data_start + padding_start + bad,
+                Some(index),
+                format!(
+                    "{} of {} padding bytes are not {null:#04x}",
+                    padding.iter().filter(|&&byte| byte != null).count(),
+                    padding.len()
+                ),
+            );
+        }
+        if padding.len() >= align {
+            self.report(
+                Severity::Warning,
+                Check::Padding,
+                data_start + padding_start,
+                Some(index),
+                format!(
+                    "{} padding bytes, more than needed for alignment",
+                    padding.len()
+                ),
+            );
+        }
+    }
+
+    /// bytes between the last item and `limit` are more items, alignment padding, or
+    /// unaccounted for.
+    fn check_trailing(&mut self, count: u64, end: usize, limit: usize) {
+        if is_alignment_padding(&self.buffer[end..limit]) {
+            return;
+        }
+
+        // items past the count mean the count is wrong, if they end exactly at the limit or
+        // are well-formed terminated items.
+        let mut pos = end;
+        let mut extra = 0;
+        while let Some((data_start, data_end, item_end)) =
+            self.layout.item_at(self.buffer, pos, limit)
+        {
+            let data = &self.buffer[data_start..data_end];
+            if self.layout.terminated && !self.layout.is_terminated(item_end - pos, data) {
+                break;
+            }
+            pos = item_end;
+            extra += 1;
+        }
+        if extra > 0 && (pos == limit || self.layout.terminated) {
+            self.report(
+                Severity::Error,
+                Check::ItemCount,
+                end,
+                None,
+                format!(
+                    "`item_count` is {count} but at least {} items are present",
+                    count + extra
+                ),
+            );
+        } else {
+            pos = end;
+        }
+
+        let trailing = &self.buffer[pos..limit];
+        if !is_alignment_padding(trailing) {
+            self.report(
+                Severity::Warning,
+                Check::TrailingBytes,
+                pos,
+                None,
+                format!(
+                    "{} bytes after the last item are not accounted for",
+                    trailing.len()
+                ),
+            );
+        }
+    }
+}
+
+/// bytes as `0xf5 0xff`.
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{byte:#04x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// nothing, or a few zero or 0xFF bytes.
+fn is_alignment_padding(bytes: &[u8]) -> bool {
+    bytes.len() <= MAX_ALIGNMENT_PADDING
+        && (bytes.iter().all(|&byte| byte == 0x00) || bytes.iter().all(|&byte| byte == 0xFF))
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` at offset {:#x}, ", self.table, self.table_offset)?;
+        if let Some(index) = self.index {
+            write!(f, "item {index} ")?;
+        }
+        write!(f, "at {:#x}: {}", self.offset, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DataDispatcherHeader, DeserializePatch, FileNameTable, NameTable, SerializePatch,
+        StringTable, TextEncoding,
+    };
+
+    /// string table of negated item data, with ids from 1.
+    fn string_table(count: u32, items: &[&[u8]]) -> Vec<u8> {
+        let mut buffer = StringTable::MAGIC_HEADER.to_vec();
+        buffer.extend(count.to_le_bytes());
+        buffer.extend(1u32.to_le_bytes());
+        for (id, data) in (1u32..).zip(items) {
+            buffer.extend(id.to_le_bytes());
+            buffer.extend((data.len() as u16).to_le_bytes());
+            buffer.extend(*data);
+        }
+        buffer
+    }
+
+    fn findings(items: &[&[u8]]) -> Vec<(Severity, Check, String)> {
+        validate(&string_table(items.len() as u32, items))
+            .into_iter()
+            .map(|finding| (finding.severity, finding.check, finding.message))
+            .collect()
+    }
+
+    #[test]
+    fn documented_terminators_are_accepted() {
+        // "AA\n" and "AA" with their padding.
+        let lf: &[u8] = &[0xBE, 0xBE, 0xF5, 0xFF, 0xFF, 0xFF];
+        let nul: &[u8] = &[0xBE, 0xBE, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert_eq!(findings(&[lf, nul]), []);
+    }
+
+    #[test]
+    fn written_tables_have_no_findings() {
+        let nul: &[u8] = &[0xBE, 0xBE, 0xFF, 0xFF, 0xFF, 0xFF];
+        let mut table =
+            StringTable::from_bytes(&string_table(2, &[nul, nul]), TextEncoding::Cp932).unwrap();
+        table.items_mut()[0].set_text("A");
+        table.items_mut()[1].set_text("ABC");
+        table.items_mut()[1].set_terminator(StringTerminator::Lf);
+        assert_eq!(validate(&table.to_bytes(TextEncoding::Cp932).unwrap()), []);
+    }
+
+    #[test]
+    fn single_null_is_an_error() {
+        assert_eq!(
+            findings(&[&[0xBE, 0xFF]]),
+            [(
+                Severity::Error,
+                Check::Terminator,
+                "the text ends with 0xff instead of 0xf5 0xff or 0xff 0xff".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn other_endings_are_errors() {
+        let cases: [(&[u8], &str); 3] = [
+            (&[0xBE, 0xBE], "the text has no terminator"),
+            (
+                &[0xBE, 0x00],
+                "the text ends with 0x00 instead of 0xf5 0xff or 0xff 0xff",
+            ),
+            (
+                &[0xBE, 0xFF, 0x00, 0x00, 0x00, 0x00],
+                "the text ends with 0xff 0x00 instead of 0xf5 0xff or 0xff 0xff",
+            ),
+        ];
+        for (data, message) in cases {
+            assert_eq!(
+                findings(&[data]),
+                [(Severity::Error, Check::Terminator, message.to_string())]
+            );
+        }
+    }
+
+    #[test]
+    fn zero_and_extra_padding_are_warnings() {
+        let zero: &[u8] = &[0xBE, 0xBE, 0xF5, 0xFF, 0x00, 0x00];
+        let extra: &[u8] = &[0xF5, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        let checks: Vec<_> = findings(&[zero, extra])
+            .into_iter()
+            .map(|(severity, check, _)| (severity, check))
+            .collect();
+        assert_eq!(
+            checks,
+            [
+                (Severity::Warning, Check::Padding),
+                (Severity::Warning, Check::Padding),
+            ]
+        );
+    }
+
+    #[test]
+    fn unaligned_item_is_an_error() {
+        let data: &[u8] = &[0xBE, 0xF5, 0xFF];
+        assert!(findings(&[data]).contains(&(
+            Severity::Error,
+            Check::Padding,
+            "the item is 9 bytes, not padded to 4 bytes".to_string()
+        )));
+    }
+
+    #[test]
+    fn item_count_mismatch_is_reported() {
+        let nul: &[u8] = &[0xBE, 0xBE, 0xFF, 0xFF, 0xFF, 0xFF];
+
+        let over = validate(&string_table(3, &[nul, nul]));
+        assert_eq!(over.len(), 1);
+        assert_eq!((over[0].check, over[0].index), (Check::ItemCount, Some(2)));
+
+        let under = validate(&string_table(1, &[nul, nul]));
+        assert_eq!(under.len(), 1);
+        assert_eq!(under[0].check, Check::ItemCount);
+        assert_eq!(
+            under[0].message,
+            "`item_count` is 1 but at least 2 items are present"
+        );
+    }
+
+    #[test]
+    fn name_and_file_name_tables_are_walked() {
+        let mut buffer = NameTable::MAGIC_HEADER.to_vec();
+        buffer.extend(0u16.to_le_bytes());
+        buffer.extend(3u16.to_le_bytes());
+        buffer.extend(4u16.to_le_bytes());
+        buffer.extend(b"Yuki");
+        let name_table_end = buffer.len();
+        buffer.extend(FileNameTable::MAGIC_HEADER);
+        buffer.extend(1u32.to_le_bytes());
+        buffer.extend(1u32.to_le_bytes());
+        buffer.extend(2u16.to_le_bytes());
+        buffer.extend([0x9E, 0x9D]);
+        buffer.extend([0x12; 3]);
+
+        let findings = validate(&buffer);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            (findings[0].table, findings[0].check, findings[0].offset),
+            (
+                NameTable::TABLE_NAME,
+                Check::ItemCount,
+                name_table_end as u64
+            )
+        );
+        assert_eq!(
+            (
+                findings[1].table,
+                findings[1].check,
+                findings[1].table_offset
+            ),
+            (
+                FileNameTable::TABLE_NAME,
+                Check::TrailingBytes,
+                name_table_end as u64
+            )
+        );
+        assert_eq!(
+            findings[1].message,
+            "3 bytes after the last item are not accounted for"
+        );
+    }
+
+    #[test]
+    fn unusual_header_value_and_truncated_header() {
+        let mut buffer = string_table(0, &[]);
+        buffer[12] = 2;
+        let findings = validate(&buffer);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, Check::HeaderValue);
+        assert_eq!(findings[0].offset, 12);
+
+        let findings = validate(&buffer[..12]);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, Check::Truncated);
+    }
+}
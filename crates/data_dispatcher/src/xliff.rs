@@ -0,0 +1,893 @@
+use crate::{DataDispatcher, DataDispatcherType, DispatcherError, DispatcherResult, ImportSummary};
+use quick_xml::{
+    Reader, XmlVersion,
+    escape::resolve_predefined_entity,
+    events::{BytesStart, Event},
+};
+use std::{fmt, fs, path::Path};
+
+const FORMAT: &str = "xliff";
+
+/// language of the original texts, the games are japanese.
+const SOURCE_LANGUAGE: &str = "ja";
+
+/// `ctype` prefix of the xliff 1.2 placeholders standing for control characters.
+const CHAR_CTYPE: &str = "x-char-";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum XliffVersion {
+    #[default]
+    V1_2,
+    V2_0,
+}
+
+/// state of a translation, the `state` of a 1.2 `<target>` or of a 2.0 `<segment>`.
+///
+/// | state                  | xliff 1.2                             | xliff 2.0    |
+/// |------------------------|---------------------------------------|--------------|
+/// | NeedsTranslation       | `new`, `needs-translation`, `needs-*` | `initial`    |
+/// | NeedsReviewTranslation | `needs-review-translation`            | `initial`    |
+/// | NeedsReviewL10n        | `needs-review-l10n`                   | `initial`    |
+/// | NeedsReviewAdaptation  | `needs-review-adaptation`             | `initial`    |
+/// | Translated             | `translated`                          | `translated` |
+/// | Reviewed               | `signed-off`                          | `reviewed`   |
+/// | Final                  | `final`                               | `final`      |
+///
+/// translations needing a review are not imported, like fuzzy po entries. xliff 2.0 has
+/// no state for them, they are written back as `initial`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum XliffState {
+    NeedsTranslation,
+    NeedsReviewTranslation,
+    NeedsReviewL10n,
+    NeedsReviewAdaptation,
+    Translated,
+    Reviewed,
+    Final,
+}
+
+impl XliffState {
+    fn as_str(self, version: XliffVersion) -> &'static str {
+        match (self, version) {
+            (XliffState::NeedsTranslation, XliffVersion::V1_2) => "needs-translation",
+            (XliffState::NeedsReviewTranslation, XliffVersion::V1_2) => "needs-review-translation",
+            (XliffState::NeedsReviewL10n, XliffVersion::V1_2) => "needs-review-l10n",
+            (XliffState::NeedsReviewAdaptation, XliffVersion::V1_2) => "needs-review-adaptation",
+            (
+                XliffState::NeedsTranslation
+                | XliffState::NeedsReviewTranslation
+                | XliffState::NeedsReviewL10n
+                | XliffState::NeedsReviewAdaptation,
+                XliffVersion::V2_0,
+            ) => "initial",
+            (XliffState::Translated, _) => "translated",
+            (XliffState::Reviewed, XliffVersion::V1_2) => "signed-off",
+            (XliffState::Reviewed, XliffVersion::V2_0) => "reviewed",
+            (XliffState::Final, _) => "final",
+        }
+    }
+
+    fn parse(state: &str, version: XliffVersion) -> Option<Self> {
+        match (state, version) {
+            ("new", XliffVersion::V1_2) => Some(XliffState::NeedsTranslation),
+            ("needs-review-translation", XliffVersion::V1_2) => {
+                Some(XliffState::NeedsReviewTranslation)
+            }
+            ("needs-review-l10n", XliffVersion::V1_2) => Some(XliffState::NeedsReviewL10n),
+            ("needs-review-adaptation", XliffVersion::V1_2) => {
+                Some(XliffState::NeedsReviewAdaptation)
+            }
+            (state, XliffVersion::V1_2) if state.starts_with("needs-") => {
+                Some(XliffState::NeedsTranslation)
+            }
+            ("signed-off", XliffVersion::V1_2) => Some(XliffState::Reviewed),
+            ("initial", XliffVersion::V2_0) => Some(XliffState::NeedsTranslation),
+            ("reviewed", XliffVersion::V2_0) => Some(XliffState::Reviewed),
+            ("translated", _) => Some(XliffState::Translated),
+            ("final", _) => Some(XliffState::Final),
+            _ => None,
+        }
+    }
+}
+
+/// trans-unit of a [XliffFile], one per table item.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XliffUnit {
+    /// `id` of string table items, the item index otherwise.
+    pub key: String,
+    /// original text, without the terminator and the padding.
+    pub source: String,
+    pub target: Option<String>,
+    pub state: Option<XliffState>,
+    /// notes for the translators, the length budget and the terminator when exported.
+    pub notes: Vec<String>,
+}
+
+impl XliffUnit {
+    /// translated, and not waiting for a translation or a review.
+    pub fn is_ready(&self) -> bool {
+        self.state
+            .is_none_or(|state| state >= XliffState::Translated)
+            && self
+                .target
+                .as_ref()
+                .is_some_and(|target| !target.is_empty())
+    }
+
+    fn target_text(&self) -> String {
+        self.target.clone().unwrap_or_default()
+    }
+}
+
+/// bilingual xliff 1.2 or 2.0 file of the texts of a known table, for the CAT tools.
+///
+/// ```{xml}
+/// <?xml version="1.0" encoding="UTF-8"?>
+/// <xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">
+///   <file original="StringTable" source-language="ja" datatype="plaintext">
+///     <body>
+///       <trans-unit id="StringTable.1024" xml:space="preserve">
+///         <source>text of the item with id 1024</source>
+///         <target state="needs-translation"></target>
+///         <note>length: 32 bytes, terminator and padding included</note>
+///         <note>terminator: null</note>
+///       </trans-unit>
+///     </body>
+///   </file>
+/// </xliff>
+/// ```
+///
+/// the `original` of the file names the table type, and the unit ids are the table type
+/// followed by the key of the item, so they stay the same across exports. control
+/// characters are written as `<cp hex="0000"/>` in 2.0 and as `<x ctype="x-char-0000"/>`
+/// placeholders in 1.2. translations are imported into the table read from the original
+/// file, which keeps the terminators and the header fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XliffFile {
+    pub version: XliffVersion,
+    pub table: DataDispatcherType,
+    pub source_language: String,
+    pub target_language: Option<String>,
+    pub units: Vec<XliffUnit>,
+}
+
+impl XliffFile {
+    /// xliff file of the texts of the table, waiting for translation.
+    pub fn export(data: &DataDispatcher, version: XliffVersion) -> DispatcherResult<Self> {
+        let unsupported = || DispatcherError::UnsupportedTable {
+            table: data.type_name().to_string(),
+            format: FORMAT,
+        };
+        let table = data.patch_type().ok_or_else(unsupported)?;
+        let units = data
+            .text_entries(FORMAT)?
+            .into_iter()
+            .map(|entry| XliffUnit {
+                notes: entry.notes(),
+                key: entry.key,
+                source: entry.text,
+                target: None,
+                state: Some(XliffState::NeedsTranslation),
+            })
+            .collect();
+        Ok(Self {
+            version,
+            table,
+            source_language: SOURCE_LANGUAGE.to_string(),
+            target_language: None,
+            units,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> DispatcherResult<Self> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(DispatcherError::from)
+            .and_then(|xliff_string| Self::from_xliff_str(&xliff_string))
+            .map_err(|e| DispatcherError::LoadFailed {
+                path: path.to_path_buf(),
+                source: Box::new(e),
+            })
+    }
+
+    /// parse a xliff 1.2 or 2.0 file, told apart by the `version` of the root element.
+    pub fn from_xliff_str(xliff_string: &str) -> DispatcherResult<Self> {
+        let mut parser = XliffParser {
+            reader: Reader::from_str(xliff_string),
+            input: xliff_string,
+            version: None,
+            table: None,
+            source_language: String::new(),
+            target_language: None,
+            units: vec![],
+            unit: None,
+            elements: vec![],
+            field: None,
+        };
+        parser.parse()?;
+
+        let version = parser
+            .version
+            .ok_or_else(|| parser.error("no `<xliff>` root element"))?;
+        let table = parser.table.ok_or_else(|| {
+            parser.error("no `<file>` element with an `original` naming the table type")
+        })?;
+        Ok(Self {
+            version,
+            table,
+            source_language: parser.source_language,
+            target_language: parser.target_language,
+            units: parser.units,
+        })
+    }
+
+    /// write the translated units into `data`, the table read from the original file.
+    pub fn apply(&self, data: &mut DataDispatcher) -> DispatcherResult<ImportSummary> {
+        let texts = self.units.iter().map(|unit| {
+            (
+                unit.key.clone(),
+                unit.is_ready().then(|| unit.target_text()),
+            )
+        });
+        data.import_texts(self.table, texts, FORMAT)
+    }
+
+    /// id of the unit of the item with `key`.
+    fn unit_id(&self, key: &str) -> String {
+        format!("{}.{key}", self.table.table_name())
+    }
+
+    fn write_v1_2(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">"
+        )?;
+        write!(
+            f,
+            "  <file original=\"{}\" source-language=\"{}\"",
+            self.table.table_name(),
+            escape_attribute(&self.source_language)
+        )?;
+        if let Some(target_language) = &self.target_language {
+            write!(
+                f,
+                " target-language=\"{}\"",
+                escape_attribute(target_language)
+            )?;
+        }
+        writeln!(f, " datatype=\"plaintext\">")?;
+        writeln!(f, "    <body>")?;
+        for unit in &self.units {
+            writeln!(
+                f,
+                "      <trans-unit id=\"{}\" xml:space=\"preserve\">",
+                escape_attribute(&self.unit_id(&unit.key))
+            )?;
+            writeln!(
+                f,
+                "        <source>{}</source>",
+                escape_text(&unit.source, self.version)
+            )?;
+            if unit.target.is_some() || unit.state.is_some() {
+                write!(f, "        <target")?;
+                if let Some(state) = unit.state {
+                    write!(f, " state=\"{}\"", state.as_str(self.version))?;
+                }
+                writeln!(
+                    f,
+                    ">{}</target>",
+                    escape_text(&unit.target_text(), self.version)
+                )?;
+            }
+            for note in &unit.notes {
+                writeln!(f, "        <note>{}</note>", escape_note(note))?;
+            }
+            writeln!(f, "      </trans-unit>")?;
+        }
+        writeln!(f, "    </body>")?;
+        writeln!(f, "  </file>")?;
+        writeln!(f, "</xliff>")
+    }
+
+    fn write_v2_0(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<xliff version=\"2.0\" xmlns=\"urn:oasis:names:tc:xliff:document:2.0\" srcLang=\"{}\"",
+            escape_attribute(&self.source_language)
+        )?;
+        if let Some(target_language) = &self.target_language {
+            write!(f, " trgLang=\"{}\"", escape_attribute(target_language))?;
+        }
+        writeln!(f, ">")?;
+        let table_name = self.table.table_name();
+        writeln!(f, "  <file id=\"{table_name}\" original=\"{table_name}\">")?;
+        for unit in &self.units {
+            writeln!(
+                f,
+                "    <unit id=\"{}\" xml:space=\"preserve\">",
+                escape_attribute(&self.unit_id(&unit.key))
+            )?;
+            if !unit.notes.is_empty() {
+                writeln!(f, "      <notes>")?;
+                for note in &unit.notes {
+                    writeln!(f, "        <note>{}</note>", escape_note(note))?;
+                }
+                writeln!(f, "      </notes>")?;
+            }
+            write!(f, "      <segment")?;
+            if let Some(state) = unit.state {
+                write!(f, " state=\"{}\"", state.as_str(self.version))?;
+            }
+            writeln!(f, ">")?;
+            writeln!(
+                f,
+                "        <source>{}</source>",
+                escape_text(&unit.source, self.version)
+            )?;
+            if let Some(target) = &unit.target {
+                writeln!(
+                    f,
+                    "        <target>{}</target>",
+                    escape_text(target, self.version)
+                )?;
+            }
+            writeln!(f, "      </segment>")?;
+            writeln!(f, "    </unit>")?;
+        }
+        writeln!(f, "  </file>")?;
+        writeln!(f, "</xliff>")
+    }
+}
+
+impl fmt::Display for XliffFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        match self.version {
+            XliffVersion::V1_2 => self.write_v1_2(f),
+            XliffVersion::V2_0 => self.write_v2_0(f),
+        }
+    }
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// escape the markup of a note, notes are plain text without placeholders.
+fn escape_note(note: &str) -> String {
+    note.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// escape the markup, and the characters xml 1.0 can not hold as the placeholders of
+/// `version`.
+fn escape_text(text: &str, version: XliffVersion) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut placeholders = 0;
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            // a raw carriage return would be read back as a line feed.
+            '\r' => escaped.push_str("&#13;"),
+            '\t' | '\n' => escaped.push(ch),
+            ch if (ch as u32) < 0x20 => match version {
+                XliffVersion::V1_2 => {
+                    placeholders += 1;
+                    escaped.push_str(&format!(
+                        "<x id=\"{placeholders}\" ctype=\"{CHAR_CTYPE}{:04X}\"/>",
+                        ch as u32
+                    ));
+                }
+                XliffVersion::V2_0 => escaped.push_str(&format!("<cp hex=\"{:04X}\"/>", ch as u32)),
+            },
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// element the text is collected into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Source,
+    Target,
+    Note,
+}
+
+struct XliffParser<'a> {
+    reader: Reader<&'a [u8]>,
+    input: &'a str,
+    version: Option<XliffVersion>,
+    table: Option<DataDispatcherType>,
+    source_language: String,
+    target_language: Option<String>,
+    units: Vec<XliffUnit>,
+    unit: Option<XliffUnit>,
+    /// local names of the open elements.
+    elements: Vec<String>,
+    /// field collecting the text, with the depth of its element.
+    field: Option<(Field, usize)>,
+}
+
+impl XliffParser<'_> {
+    fn error(&self, reason: impl Into<String>) -> DispatcherError {
+        let position = (self.reader.buffer_position() as usize).min(self.input.len());
+        let line = self.input.as_bytes()[..position]
+            .iter()
+            .filter(|&&byte| byte == b'\n')
+            .count()
+            + 1;
+        DispatcherError::InvalidTranslation {
+            format: FORMAT,
+            line,
+            reason: reason.into(),
+        }
+    }
+
+    fn parse(&mut self) -> DispatcherResult<()> {
+        loop {
+            let event = self
+                .reader
+                .read_event()
+                .map_err(|e| self.error(e.to_string()))?;
+            match event {
+                Event::Start(element) => {
+                    self.start(&element)?;
+                    self.elements.push(local_name(&element));
+                }
+                Event::Empty(element) => {
+                    self.start(&element)?;
+                    self.end(&local_name(&element))?;
+                }
+                Event::End(_) => {
+                    let name = self.elements.pop().unwrap_or_default();
+                    self.end(&name)?;
+                }
+                Event::Text(text) => {
+                    let text = text
+                        .xml10_content()
+                        .map_err(|e| self.error(e.to_string()))?;
+                    self.push_text(&text);
+                }
+                Event::CData(data) => {
+                    let data = data
+                        .xml10_content()
+                        .map_err(|e| self.error(e.to_string()))?;
+                    self.push_text(&data);
+                }
+                Event::GeneralRef(reference) => {
+                    let name = reference
+                        .xml10_content()
+                        .map_err(|e| self.error(e.to_string()))?;
+                    let ch = reference.resolve_char_ref().ok().flatten();
+                    let text = match ch {
+                        Some(ch) => ch.to_string(),
+                        None => resolve_predefined_entity(&name)
+                            .ok_or_else(|| self.error(format!("unknown entity `&{name};`")))?
+                            .to_string(),
+                    };
+                    self.push_text(&text);
+                }
+                Event::Eof => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    fn attribute(&self, element: &BytesStart, name: &str) -> DispatcherResult<Option<String>> {
+        element
+            .try_get_attribute(name)
+            .map_err(|e| self.error(e.to_string()))?
+            .map(|attribute| attribute.normalized_value(XmlVersion::Implicit1_0))
+            .transpose()
+            .map(|value| value.map(|value| value.into_owned()))
+            .map_err(|e| self.error(e.to_string()))
+    }
+
+    fn parent(&self) -> &str {
+        self.elements.last().map_or("", String::as_str)
+    }
+
+    fn start(&mut self, element: &BytesStart) -> DispatcherResult<()> {
+        let name = local_name(element);
+        let name = name.as_str();
+        if self.field.is_some() {
+            return self.inline(name, element);
+        }
+        let version = self.version.unwrap_or_default();
+        match name {
+            "xliff" => {
+                let version = self.attribute(element, "version")?.unwrap_or_default();
+                self.version = Some(match version.as_str() {
+                    "1.2" => XliffVersion::V1_2,
+                    version if version.starts_with("2.") => XliffVersion::V2_0,
+                    version => {
+                        return Err(self.error(format!("unsupported xliff version `{version}`")));
+                    }
+                });
+                if let Some(source_language) = self.attribute(element, "srcLang")? {
+                    self.source_language = source_language;
+                }
+                self.target_language = self.attribute(element, "trgLang")?;
+            }
+            "file" => {
+                let table_name = self.attribute(element, "original")?.unwrap_or_default();
+                let table = DataDispatcherType::from_table_name(&table_name)
+                    .ok_or_else(|| self.error(format!("unknown table type `{table_name}`")))?;
+                if self.table.is_some_and(|known| known != table) {
+                    return Err(self.error("files of several table types"));
+                }
+                self.table = Some(table);
+                if let Some(source_language) = self.attribute(element, "source-language")? {
+                    self.source_language = source_language;
+                }
+                if let Some(target_language) = self.attribute(element, "target-language")? {
+                    self.target_language = Some(target_language);
+                }
+            }
+            "trans-unit" | "unit" => {
+                let id = self
+                    .attribute(element, "id")?
+                    .ok_or_else(|| self.error(format!("`<{name}>` without an `id`")))?;
+                let prefix = self.table.map(|table| format!("{}.", table.table_name()));
+                let key = match prefix.as_deref().and_then(|prefix| id.strip_prefix(prefix)) {
+                    Some(key) => key.to_string(),
+                    // reported as an unknown key.
+                    None => id,
+                };
+                self.unit = Some(XliffUnit {
+                    key,
+                    ..XliffUnit::default()
+                });
+            }
+            "segment" => {
+                let state = self.state(element, version)?;
+                if let Some(unit) = &mut self.unit {
+                    // the least advanced segment gives the state of the unit.
+                    unit.state = match (unit.state, state) {
+                        (Some(current), Some(state)) => Some(current.min(state)),
+                        (current, state) => current.or(state),
+                    };
+                }
+            }
+            "source" | "target" if self.in_unit() => {
+                let field = match name {
+                    "source" => Field::Source,
+                    _ => Field::Target,
+                };
+                let state = match version {
+                    XliffVersion::V1_2 => self.state(element, version)?,
+                    XliffVersion::V2_0 => None,
+                };
+                if let Some(unit) = &mut self.unit {
+                    if field == Field::Target {
+                        unit.target.get_or_insert_default();
+                        unit.state = unit.state.or(state);
+                    }
+                }
+                self.field = Some((field, self.elements.len()));
+            }
+            "note" if self.unit.is_some() => {
+                if let Some(unit) = &mut self.unit {
+                    unit.notes.push(String::new());
+                }
+                self.field = Some((Field::Note, self.elements.len()));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// the source or target is a child of the unit in 1.2, of a segment or an ignorable in
+    /// 2.0. alternative translations and segmented sources are left out.
+    fn in_unit(&self) -> bool {
+        self.unit.is_some() && matches!(self.parent(), "trans-unit" | "segment" | "ignorable")
+    }
+
+    fn state(
+        &self,
+        element: &BytesStart,
+        version: XliffVersion,
+    ) -> DispatcherResult<Option<XliffState>> {
+        self.attribute(element, "state")?
+            .map(|state| {
+                XliffState::parse(&state, version)
+                    .ok_or_else(|| self.error(format!("unknown state `{state}`")))
+            })
+            .transpose()
+    }
+
+    /// inline element of a source, target or note: the placeholders of control characters
+    /// are resolved in sources and targets, the other tags are dropped and their text kept.
+    fn inline(&mut self, name: &str, element: &BytesStart) -> DispatcherResult<()> {
+        if matches!(self.field, Some((Field::Note, _))) {
+            return Ok(());
+        }
+        let code = match name {
+            "cp" => self.attribute(element, "hex")?,
+            "x" => self
+                .attribute(element, "ctype")?
+                .and_then(|ctype| ctype.strip_prefix(CHAR_CTYPE).map(str::to_string)),
+            _ => None,
+        };
+        if let Some(code) = code {
+            let ch = u32::from_str_radix(&code, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| self.error(format!("invalid character code `{code}`")))?;
+            self.push_text(&ch.to_string());
+        }
+        Ok(())
+    }
+
+    fn end(&mut self, name: &str) -> DispatcherResult<()> {
+        if let Some((_, depth)) = self.field {
+            if self.elements.len() == depth {
+                self.field = None;
+            }
+            return Ok(());
+        }
+        if matches!(name, "trans-unit" | "unit") {
+            if let Some(unit) = self.unit.take() {
+                self.units.push(unit);
+            }
+        }
+        Ok(())
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let (Some((field, _)), Some(unit)) = (self.field, &mut self.unit) else {
+            return;
+        };
+        match field {
+            Field::Source => unit.source.push_str(text),
+            Field::Target => unit.target.get_or_insert_default().push_str(text),
+            Field::Note => {
+                if let Some(note) = unit.notes.last_mut() {
+                    note.push_str(text);
+                }
+            }
+        }
+    }
+}
+
+/// local name of the element, the input is utf-8 text.
+fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(version: XliffVersion) -> XliffFile {
+        XliffFile {
+            version,
+            table: DataDispatcherType::StringTable,
+            source_language: SOURCE_LANGUAGE.to_string(),
+            target_language: Some("en".to_string()),
+            units: vec![
+                XliffUnit {
+                    key: "1024".to_string(),
+                    source: "「結希」\n<b>&</b>\r\t\0\x1B".to_string(),
+                    target: Some("\"Yuki\"\n\0".to_string()),
+                    state: Some(XliffState::Reviewed),
+                    notes: vec![
+                        "length: 32 bytes".to_string(),
+                        "a < b & <x id=\"1\"/>\t".to_string(),
+                    ],
+                },
+                XliffUnit {
+                    key: "1025".to_string(),
+                    source: "お兄さん".to_string(),
+                    target: Some(String::new()),
+                    state: Some(XliffState::NeedsTranslation),
+                    notes: vec![],
+                },
+            ],
+        }
+    }
+
+    fn parse_error(xliff_string: &str) -> String {
+        match XliffFile::from_xliff_str(xliff_string) {
+            Err(error) => error.to_string(),
+            Ok(xliff) => panic!("expected an error, got {xliff:?}"),
+        }
+    }
+
+    #[test]
+    fn written_file_is_parsed_back() {
+        for version in [XliffVersion::V1_2, XliffVersion::V2_0] {
+            let xliff = file(version);
+            let xliff_string = xliff.to_string();
+            assert_eq!(XliffFile::from_xliff_str(&xliff_string).unwrap(), xliff);
+        }
+    }
+
+    #[test]
+    fn control_characters_are_written_as_placeholders() {
+        let v1_2 = file(XliffVersion::V1_2).to_string();
+        assert!(v1_2.contains(
+            "&lt;b&gt;&amp;&lt;/b&gt;&#13;\t<x id=\"1\" ctype=\"x-char-0000\"/>\
+             <x id=\"2\" ctype=\"x-char-001B\"/></source>"
+        ));
+        assert!(v1_2.contains("<target state=\"signed-off\">"));
+
+        let v2_0 = file(XliffVersion::V2_0).to_string();
+        assert!(v2_0.contains("&#13;\t<cp hex=\"0000\"/><cp hex=\"001B\"/></source>"));
+        assert!(v2_0.contains("<segment state=\"reviewed\">"));
+    }
+
+    #[test]
+    fn notes_are_plain_text() {
+        for version in [XliffVersion::V1_2, XliffVersion::V2_0] {
+            let xliff_string = file(version).to_string();
+            assert!(xliff_string.contains("<note>a &lt; b &amp; &lt;x id=\"1\"/&gt;\t</note>"));
+        }
+
+        let xliff_string = r#"<xliff version="1.2">
+  <file original="NameTable">
+    <trans-unit id="NameTable.0">
+      <source>結希</source>
+      <note>see <x ctype="x-char-0000"/><g>Yuki</g></note>
+    </trans-unit>
+  </file>
+</xliff>"#;
+        let xliff = XliffFile::from_xliff_str(xliff_string).unwrap();
+        assert_eq!(xliff.units[0].notes, ["see Yuki"]);
+    }
+
+    #[test]
+    fn review_states_survive_a_round_trip() {
+        let states = [
+            (
+                "needs-review-translation",
+                XliffState::NeedsReviewTranslation,
+            ),
+            ("needs-review-l10n", XliffState::NeedsReviewL10n),
+            ("needs-review-adaptation", XliffState::NeedsReviewAdaptation),
+            ("needs-adaptation", XliffState::NeedsTranslation),
+        ];
+        for (state, expected) in states {
+            assert_eq!(XliffState::parse(state, XliffVersion::V1_2), Some(expected));
+            let mut xliff = file(XliffVersion::V1_2);
+            xliff.units[0].state = Some(expected);
+            let xliff_string = xliff.to_string();
+            assert_eq!(XliffFile::from_xliff_str(&xliff_string).unwrap(), xliff);
+            assert!(!xliff.units[0].is_ready());
+
+            // no review states in xliff 2.0.
+            xliff.version = XliffVersion::V2_0;
+            assert!(xliff.to_string().contains("<segment state=\"initial\">"));
+        }
+    }
+
+    #[test]
+    fn files_of_other_tools_are_read() {
+        let xliff_string = r#"<?xml version="1.0" encoding="UTF-8"?>
+<x:xliff version="1.2" xmlns:x="urn:oasis:names:tc:xliff:document:1.2">
+  <x:file original="NameTable" source-language="ja-JP" target-language="fr" datatype="plaintext">
+    <x:body>
+      <x:trans-unit id="NameTable.0">
+        <x:source>結希</x:source>
+        <x:target state="needs-review-translation"><g id="1">Yu</g>ki&#x21;</x:target>
+        <x:alt-trans><x:target>Yuuki</x:target></x:alt-trans>
+      </x:trans-unit>
+      <x:trans-unit id="1">
+        <x:source><![CDATA[<raw>]]></x:source>
+        <x:target state="final">&lt;raw&gt;</x:target>
+      </x:trans-unit>
+    </x:body>
+  </x:file>
+</x:xliff>
+"#;
+        let xliff = XliffFile::from_xliff_str(xliff_string).unwrap();
+        assert_eq!(xliff.version, XliffVersion::V1_2);
+        assert_eq!(xliff.table, DataDispatcherType::NameTable);
+        assert_eq!(
+            (
+                xliff.source_language.as_str(),
+                xliff.target_language.as_deref()
+            ),
+            ("ja-JP", Some("fr"))
+        );
+        assert_eq!(
+            xliff.units,
+            [
+                XliffUnit {
+                    key: "0".to_string(),
+                    source: "結希".to_string(),
+                    target: Some("Yuki!".to_string()),
+                    state: Some(XliffState::NeedsReviewTranslation),
+                    notes: vec![],
+                },
+                XliffUnit {
+                    key: "1".to_string(),
+                    source: "<raw>".to_string(),
+                    target: Some("<raw>".to_string()),
+                    state: Some(XliffState::Final),
+                    notes: vec![],
+                },
+            ]
+        );
+        assert!(!xliff.units[0].is_ready());
+        assert!(xliff.units[1].is_ready());
+    }
+
+    #[test]
+    fn least_advanced_segment_gives_the_state() {
+        let xliff_string = r#"<xliff version="2.1" srcLang="ja">
+  <file id="f" original="FileNameTable">
+    <unit id="FileNameTable.3">
+      <segment state="final"><source>a</source><target>A</target></segment>
+      <ignorable><source> </source><target> </target></ignorable>
+      <segment state="translated"><source>b</source><target>B</target></segment>
+    </unit>
+  </file>
+</xliff>"#;
+        let xliff = XliffFile::from_xliff_str(xliff_string).unwrap();
+        assert_eq!(xliff.version, XliffVersion::V2_0);
+        let unit = &xliff.units[0];
+        assert_eq!(unit.key, "3");
+        assert_eq!(
+            (unit.source.as_str(), unit.target.as_deref()),
+            ("a b", Some("A B"))
+        );
+        assert_eq!(unit.state, Some(XliffState::Translated));
+    }
+
+    #[test]
+    fn invalid_files_are_errors() {
+        let cases = [
+            (
+                "<file original=\"StringTable\"/>",
+                "no `<xliff>` root element",
+            ),
+            ("<xliff version=\"1.2\"/>", "no `<file>` element"),
+            (
+                "<xliff version=\"1.0\"/>",
+                "unsupported xliff version `1.0`",
+            ),
+            (
+                "<xliff version=\"1.2\">\n<file original=\"Other\"/></xliff>",
+                "line 2: unknown table type `Other`",
+            ),
+            (
+                "<xliff version=\"1.2\"><file original=\"StringTable\"/>\
+                 <file original=\"NameTable\"/></xliff>",
+                "files of several table types",
+            ),
+            (
+                "<xliff version=\"1.2\"><file original=\"StringTable\"><trans-unit>",
+                "`<trans-unit>` without an `id`",
+            ),
+            (
+                "<xliff version=\"2.0\"><file original=\"StringTable\"><unit id=\"1\">\
+                 <segment state=\"signed-off\">",
+                "unknown state `signed-off`",
+            ),
+            (
+                "<xliff version=\"2.0\"><file original=\"StringTable\"><unit id=\"1\">\
+                 <segment><source><cp hex=\"D800\"/>",
+                "invalid character code `D800`",
+            ),
+            (
+                "<xliff version=\"2.0\"><file original=\"StringTable\"><unit id=\"1\">\
+                 <segment><source>&unknown;",
+                "unknown entity `&unknown;`",
+            ),
+        ];
+        for (xliff_string, reason) in cases {
+            let error = parse_error(xliff_string);
+            assert!(error.starts_with("invalid xliff file at line"), "{error}");
+            assert!(error.contains(reason), "{error}");
+        }
+    }
+}
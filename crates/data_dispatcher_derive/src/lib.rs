@@ -0,0 +1,402 @@
+//! derive macro of data_dispatcher, see [macro@BinaryPatch].
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Data, DeriveInput, Error, Fields, Ident, LitByteStr, LitInt, LitStr, Meta, Result, Token, Type,
+    parse::ParseStream, parse_macro_input,
+};
+
+/// derive `DeserializePatch` and `SerializePatch` from the field attributes, fields are
+/// read and written in declaration order:
+///
+/// - `#[magic(b"[STRTBL]")]` on the struct: the patch starts with this magic header,
+///   `DataDispatcherHeader` is derived too, with the struct name as table name;
+/// - `#[le]`: little-endian integer;
+/// - `#[count_of(items)]`: with `#[le]`, the item count of the `#[items]` field, written
+///   from its length;
+/// - `#[items]`: `Vec` of items, each one implementing both traits;
+/// - `#[len_prefix(u16)]`: string with a length prefix of the given integer type, add
+///   `store = length` to keep the length as read in the `length` field;
+/// - `#[encoding]`: with `#[len_prefix]`, the string is decoded with the encoding given
+///   to the traits, or always with the given one, e.g. `#[encoding(shift_jis)]`;
+/// - `#[negate]`: with `#[len_prefix]`, the string bytes are bitwise negated;
+/// - `#[align(4)]`: with `#[len_prefix]`, the string is padded with nulls until the
+///   struct is 4 bytes aligned, the padding is counted in the length prefix;
+/// - `#[split(split_data, join_data)]`: with `#[len_prefix]`, the decoded string is passed
+///   to `fn split_data(&mut self, &str)` and written back from `fn join_data(&self) -> String`
+///   instead of being stored in the field;
+/// - `#[skip]`: not part of the binary layout, left to its default.
+///
+/// the struct has to implement [Default].
+#[proc_macro_derive(
+    BinaryPatch,
+    attributes(
+        magic, le, count_of, items, len_prefix, encoding, negate, align, split, skip
+    )
+)]
+pub fn derive_binary_patch(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct LayoutField {
+    ident: Ident,
+    ty: Type,
+    kind: FieldKind,
+}
+
+enum FieldKind {
+    Le { count_of: Option<Ident> },
+    Items,
+    Text(Box<TextField>),
+    Skip,
+}
+
+struct TextField {
+    prefix: Type,
+    store: Option<Ident>,
+    /// `None` for the encoding given to the traits.
+    encoding: Option<Ident>,
+    negate: bool,
+    align: Option<u64>,
+    split: Option<(Ident, Ident)>,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let table_name = LitStr::new(&ident.to_string(), ident.span());
+
+    let mut magic = None;
+    for attr in &input.attrs {
+        if attr.path().is_ident("magic") {
+            magic = Some(attr.parse_args::<LitByteStr>()?);
+        }
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(|field| {
+                    Ok(LayoutField {
+                        ident: field.ident.clone().expect("named field"),
+                        ty: field.ty.clone(),
+                        kind: field_kind(field)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            _ => {
+                return Err(Error::new_spanned(
+                    ident,
+                    "expected a struct with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "expected a struct with named fields",
+            ));
+        }
+    };
+
+    let krate = quote!(::data_dispatcher);
+    let support = quote!(#krate::derive_support);
+    let uses_start = magic.is_some()
+        || fields
+            .iter()
+            .any(|field| matches!(&field.kind, FieldKind::Text(text) if text.align.is_some()));
+
+    let mut reads = vec![];
+    let mut writes = vec![];
+
+    if let Some(magic) = &magic {
+        reads.push(quote! {
+            reader.read_magic("header", #magic).map_err(in_header)?;
+        });
+        writes.push(quote! {
+            writer.write_bytes("header", #magic)?;
+        });
+    }
+
+    // fields before the items belong to the header of the table.
+    let mut in_header = magic.is_some();
+    for field in &fields {
+        let field_ident = &field.ident;
+        let field_name = LitStr::new(&field_ident.to_string(), field_ident.span());
+        let ty = &field.ty;
+        let header_context = match in_header {
+            true => quote!(.map_err(in_header)),
+            false => quote!(),
+        };
+
+        match &field.kind {
+            FieldKind::Le { count_of } => {
+                reads.push(quote! {
+                    value.#field_ident = reader.read_le(#field_name)#header_context?;
+                });
+                writes.push(match count_of {
+                    Some(items) => quote! {
+                        writer.write_le(
+                            #field_name,
+                            #support::item_count::<#ty>(#table_name, self.#items.len())?,
+                        )?;
+                    },
+                    None => quote! {
+                        writer.write_le(#field_name, self.#field_ident)?;
+                    },
+                });
+            }
+            FieldKind::Items => {
+                in_header = false;
+                let count = fields
+                    .iter()
+                    .find_map(|other| match &other.kind {
+                        FieldKind::Le {
+                            count_of: Some(items),
+                        } if items == field_ident => Some(&other.ident),
+                        _ => None,
+                    })
+                    .ok_or_else(|| {
+                        Error::new_spanned(
+                            field_ident,
+                            "`#[items]` needs a `#[count_of(..)]` field before it",
+                        )
+                    })?;
+                reads.push(quote! {
+                    value.#field_ident =
+                        #support::read_items(reader, encoding, #table_name, value.#count as usize)?;
+                });
+                writes.push(quote! {
+                    #support::write_items(writer, encoding, #table_name, &self.#field_ident)?;
+                });
+            }
+            FieldKind::Text(text) => {
+                let prefix = &text.prefix;
+                let encoding = match &text.encoding {
+                    Some(encoding) => {
+                        let variant = encoding_variant(encoding)?;
+                        quote!(#krate::TextEncoding::#variant)
+                    }
+                    None => quote!(encoding),
+                };
+
+                let store = text.store.as_ref().map(|store| {
+                    quote! {
+                        value.#store = length;
+                    }
+                });
+                let negate = text.negate.then(|| {
+                    quote! {
+                        #support::negate(&mut raw_data);
+                    }
+                });
+                let assign = match &text.split {
+                    Some((split, _)) => quote!(value.#split(&data);),
+                    None => quote!(value.#field_ident = data;),
+                };
+                reads.push(quote! {
+                    let (length, mut raw_data) =
+                        reader.read_prefixed::<#prefix>(#field_name)#header_context?;
+                    #store
+                    #negate
+                    let data = #encoding.decode(&raw_data);
+                    #assign
+                });
+
+                let data = match &text.split {
+                    Some((_, join)) => quote!(&self.#join()),
+                    None => quote!(&self.#field_ident),
+                };
+                let align = text.align.map(|align| {
+                    quote! {
+                        let head_length = writer.stream_position()? - start_pos
+                            + ::std::mem::size_of::<#prefix>() as u64;
+                        #support::pad_to_alignment(&mut raw_data, head_length, #align);
+                    }
+                });
+                writes.push(quote! {
+                    let mut raw_data = #encoding.encode(#data)?;
+                    #align
+                    #negate
+                    writer.write_prefixed::<#prefix>(#field_name, &raw_data)?;
+                });
+            }
+            FieldKind::Skip => {}
+        }
+    }
+
+    let read_start = uses_start.then(|| {
+        quote! {
+            let start_pos = reader.stream_position()?;
+        }
+    });
+    let read_header_context = magic.is_some().then(|| {
+        quote! {
+            let in_header = #support::in_header(#table_name, start_pos);
+        }
+    });
+    let write_start = uses_start.then(|| {
+        quote! {
+            let start_pos = writer.stream_position()?;
+        }
+    });
+    let header_impl = magic.as_ref().map(|magic| {
+        quote! {
+            impl #krate::DataDispatcherHeader for #ident {
+                const MAGIC_HEADER: &[u8] = #magic;
+                const TABLE_NAME: &str = #table_name;
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #krate::DeserializePatch for #ident {
+            #[allow(unused_variables, unused_mut, clippy::field_reassign_with_default)]
+            fn deserialize_patch<R: ::std::io::Read + ::std::io::Seek>(
+                reader: &mut R,
+                encoding: #krate::TextEncoding,
+            ) -> #krate::DispatcherResult<Self> {
+                use ::std::io::Seek as _;
+                use #support::ReadBinary as _;
+
+                #read_start
+                #read_header_context
+                let mut value = Self::default();
+                #(#reads)*
+                Ok(value)
+            }
+        }
+
+        impl #krate::SerializePatch for #ident {
+            #[allow(unused_variables)]
+            fn serialize_patch<W: ::std::io::Write + ::std::io::Seek>(
+                &self,
+                writer: &mut W,
+                encoding: #krate::TextEncoding,
+            ) -> #krate::DispatcherResult<()> {
+                use ::std::io::Seek as _;
+                use #support::WriteBinary as _;
+
+                #write_start
+                #(#writes)*
+                Ok(())
+            }
+        }
+
+        #header_impl
+    })
+}
+
+fn field_kind(field: &syn::Field) -> Result<FieldKind> {
+    let mut le = false;
+    let mut count_of = None;
+    let mut items = false;
+    let mut skip = false;
+    let mut text: Option<TextField> = None;
+    let mut encoding = None;
+    let mut negate = false;
+    let mut align = None;
+    let mut split = None;
+
+    for attr in &field.attrs {
+        let path = attr.path();
+        if path.is_ident("le") {
+            le = true;
+        } else if path.is_ident("count_of") {
+            count_of = Some(attr.parse_args::<Ident>()?);
+        } else if path.is_ident("items") {
+            items = true;
+        } else if path.is_ident("skip") {
+            skip = true;
+        } else if path.is_ident("len_prefix") {
+            text = Some(attr.parse_args_with(|input: ParseStream| {
+                let prefix = input.parse::<Type>()?;
+                let mut store = None;
+                if input.parse::<Option<Token![,]>>()?.is_some() {
+                    let key = input.parse::<Ident>()?;
+                    if key != "store" {
+                        return Err(Error::new_spanned(key, "expected `store = field`"));
+                    }
+                    input.parse::<Token![=]>()?;
+                    store = Some(input.parse::<Ident>()?);
+                }
+                Ok(TextField {
+                    prefix,
+                    store,
+                    encoding: None,
+                    negate: false,
+                    align: None,
+                    split: None,
+                })
+            })?);
+        } else if path.is_ident("encoding") {
+            encoding = Some(match &attr.meta {
+                Meta::Path(_) => None,
+                _ => Some(attr.parse_args::<Ident>()?),
+            });
+        } else if path.is_ident("negate") {
+            negate = true;
+        } else if path.is_ident("align") {
+            align = Some(attr.parse_args::<LitInt>()?.base10_parse::<u64>()?);
+        } else if path.is_ident("split") {
+            split = Some(attr.parse_args_with(|input: ParseStream| {
+                let split = input.parse::<Ident>()?;
+                input.parse::<Token![,]>()?;
+                let join = input.parse::<Ident>()?;
+                Ok((split, join))
+            })?);
+        }
+    }
+
+    let ident = field.ident.as_ref().expect("named field");
+    if let Some(mut text) = text {
+        text.encoding = encoding.ok_or_else(|| {
+            Error::new_spanned(ident, "`#[len_prefix(..)]` needs an `#[encoding]`")
+        })?;
+        text.negate = negate;
+        text.align = align;
+        text.split = split;
+        return Ok(FieldKind::Text(Box::new(text)));
+    }
+    if encoding.is_some() || negate || align.is_some() || split.is_some() {
+        return Err(Error::new_spanned(
+            ident,
+            "`#[encoding]`, `#[negate]`, `#[align]` and `#[split]` need a `#[len_prefix(..)]`",
+        ));
+    }
+    match (le, items, skip) {
+        (true, false, false) => Ok(FieldKind::Le { count_of }),
+        (false, true, false) => Ok(FieldKind::Items),
+        (false, false, true) => Ok(FieldKind::Skip),
+        _ if count_of.is_some() => Err(Error::new_spanned(
+            ident,
+            "`#[count_of(..)]` needs a `#[le]`",
+        )),
+        _ => Err(Error::new_spanned(
+            ident,
+            "expected exactly one of `#[le]`, `#[len_prefix(..)]`, `#[items]` or `#[skip]`",
+        )),
+    }
+}
+
+fn encoding_variant(encoding: &Ident) -> Result<Ident> {
+    let variant = match encoding.to_string().as_str() {
+        "shift_jis" => "ShiftJis",
+        "cp932" => "Cp932",
+        "gbk" => "Gbk",
+        "utf8" => "Utf8",
+        _ => {
+            return Err(Error::new_spanned(
+                encoding,
+                "expected `shift_jis`, `cp932`, `gbk` or `utf8`",
+            ));
+        }
+    };
+    Ok(Ident::new(variant, encoding.span()))
+}
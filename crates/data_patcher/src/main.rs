@@ -1,3 +1,61 @@
-fn main() {
-    todo!()
+use anyhow::Result as AnyResult;
+use clap::Parser;
+use data_dispatcher::{DataDispatcher, DumpFormat, Manifest, TextEncoding};
+use std::{fs, path::PathBuf};
+#[allow(unused_imports)]
+use utils::IntoAnyResult;
+
+/// write a dump or a translation file back into the original file or darc archive, like
+/// `data_dispatcher patch`.
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct ConsoleArgs {
+    /// Ron, json or yaml dump, or po, xliff, spreadsheet or project file of translations, to
+    /// write back. Told apart by the extension
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Original file or darc archive
+    #[arg(short, long)]
+    container: PathBuf,
+
+    /// Output file
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Text encoding of the table items, the manifest target encoding or cp932 if omitted
+    #[arg(short, long, value_enum)]
+    encoding: Option<TextEncoding>,
+
+    /// Project manifest, `imojiru.toml` of the working directory if omitted
+    #[arg(short, long)]
+    manifest: Option<PathBuf>,
+}
+
+fn main() -> AnyResult<()> {
+    let args = ConsoleArgs::parse();
+
+    let manifest = Manifest::locate(args.manifest.as_deref())?;
+    let encoding = args
+        .encoding
+        .or(manifest.as_ref().map(Manifest::target_encoding))
+        .unwrap_or_default();
+    let container = fs::read(&args.container)?;
+
+    let (data, summary) = DataDispatcher::load_patch(
+        &args.input,
+        DumpFormat::from_path(&args.input),
+        &container,
+        encoding,
+        0,
+    )?;
+    for key in &summary.unknown_keys {
+        eprintln!(
+            "warning: {}: no item `{key}` in the table, skipped",
+            args.input.display()
+        );
+    }
+
+    fs::write(&args.output, data.patch(&container, encoding)?)?;
+    Ok(())
 }
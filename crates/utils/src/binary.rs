@@ -0,0 +1,264 @@
+use std::{
+    fmt,
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+};
+use thiserror::Error;
+
+pub type BinaryResult<T> = Result<T, BinaryError>;
+
+/// bytes reserved up front by [ReadBinary::read_bytes], longer fields grow as they are read
+/// so that a corrupt length can't allocate more than the stream holds.
+const RESERVED_LENGTH: usize = 0x1_0000;
+
+/// io error raised by [ReadBinary] or [WriteBinary], with the field and the offset
+/// it was raised at.
+#[derive(Debug, Error)]
+#[error("failed to {access} `{field}` at offset {offset:#x}")]
+pub struct BinaryError {
+    pub access: BinaryAccess,
+    pub field: String,
+    pub offset: u64,
+    #[source]
+    pub source: io::Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryAccess {
+    Read,
+    Write,
+}
+
+impl fmt::Display for BinaryAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryAccess::Read => f.write_str("read"),
+            BinaryAccess::Write => f.write_str("write"),
+        }
+    }
+}
+
+impl BinaryError {
+    pub fn new(access: BinaryAccess, field: &str, offset: u64, source: io::Error) -> Self {
+        Self {
+            access,
+            field: field.to_string(),
+            offset,
+            source,
+        }
+    }
+
+    /// the data ended before the field.
+    pub fn is_eof(&self) -> bool {
+        self.source.kind() == ErrorKind::UnexpectedEof
+    }
+}
+
+/// little-endian integer handled by [ReadBinary] and [WriteBinary].
+pub trait LeInt: Copy + TryFrom<usize> + TryInto<usize> {
+    const SIZE: usize;
+
+    fn from_le_slice(bytes: &[u8]) -> Self;
+
+    fn to_le_vec(self) -> Vec<u8>;
+}
+
+macro_rules! impl_le_int {
+    ($($int:ty),*) => {
+        $(
+            impl LeInt for $int {
+                const SIZE: usize = size_of::<$int>();
+
+                fn from_le_slice(bytes: &[u8]) -> Self {
+                    let mut le_bytes = [0; size_of::<$int>()];
+                    le_bytes.copy_from_slice(bytes);
+                    <$int>::from_le_bytes(le_bytes)
+                }
+
+                fn to_le_vec(self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_le_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// round `value` up to a multiple of `alignment`.
+pub fn align_up(value: u64, alignment: u64) -> u64 {
+    value.next_multiple_of(alignment)
+}
+
+/// length of the whole stream, the position is kept.
+pub fn stream_len<S: Seek + ?Sized>(stream: &mut S) -> io::Result<u64> {
+    let pos = stream.stream_position()?;
+    let len = stream.seek(SeekFrom::End(0))?;
+    stream.seek(SeekFrom::Start(pos))?;
+    Ok(len)
+}
+
+/// field readers for binary files, errors carry the field name and its offset.
+///
+/// ## Note
+/// length-prefixed strings are read as raw bytes, decoding them is up to the caller.
+pub trait ReadBinary: Read + Seek {
+    /// read exactly `length` bytes, the data ending before is an eof error.
+    fn read_bytes(&mut self, field: &str, length: usize) -> BinaryResult<Vec<u8>> {
+        let offset = field_offset(self, BinaryAccess::Read, field)?;
+        let mut bytes = Vec::with_capacity(length.min(RESERVED_LENGTH));
+        self.take(length as u64)
+            .read_to_end(&mut bytes)
+            .map_err(|e| BinaryError::new(BinaryAccess::Read, field, offset, e))?;
+        if bytes.len() < length {
+            let source = io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("{length} bytes expected, {} left", bytes.len()),
+            );
+            return Err(BinaryError::new(BinaryAccess::Read, field, offset, source));
+        }
+        Ok(bytes)
+    }
+
+    fn read_le<T: LeInt>(&mut self, field: &str) -> BinaryResult<T> {
+        let bytes = self.read_bytes(field, T::SIZE)?;
+        Ok(T::from_le_slice(&bytes))
+    }
+
+    /// read a `T` length followed by as many bytes.
+    fn read_prefixed<T: LeInt>(&mut self, field: &str) -> BinaryResult<(T, Vec<u8>)> {
+        let offset = field_offset(self, BinaryAccess::Read, field)?;
+        let length = self.read_le::<T>(field)?;
+        let bytes_length = length.try_into().map_err(|_| {
+            let source = io::Error::new(ErrorKind::InvalidData, "negative length prefix");
+            BinaryError::new(BinaryAccess::Read, field, offset, source)
+        })?;
+        Ok((length, self.read_bytes(field, bytes_length)?))
+    }
+
+    /// read the magic header and make sure it matches `magic`.
+    fn read_magic(&mut self, field: &str, magic: &[u8]) -> BinaryResult<()> {
+        let offset = field_offset(self, BinaryAccess::Read, field)?;
+        let bytes = self.read_bytes(field, magic.len())?;
+        if bytes != magic {
+            let source = io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "expected `{}`, found `{}`",
+                    String::from_utf8_lossy(magic),
+                    String::from_utf8_lossy(&bytes)
+                ),
+            );
+            return Err(BinaryError::new(BinaryAccess::Read, field, offset, source));
+        }
+        Ok(())
+    }
+
+    /// skip the padding up to the next multiple of `alignment`.
+    fn skip_to_alignment(&mut self, field: &str, alignment: u64) -> BinaryResult<()> {
+        let offset = field_offset(self, BinaryAccess::Read, field)?;
+        let padding = align_up(offset, alignment) - offset;
+        self.read_bytes(field, padding as usize)?;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek + ?Sized> ReadBinary for R {}
+
+/// field writers for binary files, symmetric to [ReadBinary].
+pub trait WriteBinary: Write + Seek {
+    fn write_bytes(&mut self, field: &str, bytes: &[u8]) -> BinaryResult<()> {
+        let offset = field_offset(self, BinaryAccess::Write, field)?;
+        self.write_all(bytes)
+            .map_err(|e| BinaryError::new(BinaryAccess::Write, field, offset, e))
+    }
+
+    fn write_le<T: LeInt>(&mut self, field: &str, value: T) -> BinaryResult<()> {
+        self.write_bytes(field, &value.to_le_vec())
+    }
+
+    /// write the length of `bytes` as a `T`, then the bytes.
+    fn write_prefixed<T: LeInt>(&mut self, field: &str, bytes: &[u8]) -> BinaryResult<()> {
+        let offset = field_offset(self, BinaryAccess::Write, field)?;
+        let length = T::try_from(bytes.len()).map_err(|_| {
+            let source = io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} bytes don't fit in a {} bytes length prefix",
+                    bytes.len(),
+                    T::SIZE
+                ),
+            );
+            BinaryError::new(BinaryAccess::Write, field, offset, source)
+        })?;
+        self.write_le(field, length)?;
+        self.write_bytes(field, bytes)
+    }
+
+    /// write null padding up to the next multiple of `alignment`.
+    fn pad_to_alignment(&mut self, field: &str, alignment: u64) -> BinaryResult<()> {
+        let offset = field_offset(self, BinaryAccess::Write, field)?;
+        let padding = align_up(offset, alignment) - offset;
+        self.write_bytes(field, &vec![0; padding as usize])
+    }
+}
+
+impl<W: Write + Seek + ?Sized> WriteBinary for W {}
+
+fn field_offset<S: Seek + ?Sized>(
+    stream: &mut S,
+    access: BinaryAccess,
+    field: &str,
+) -> BinaryResult<u64> {
+    stream
+        .stream_position()
+        .map_err(|e| BinaryError::new(access, field, 0, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn fields_round_trip() {
+        let mut writer = Cursor::new(vec![]);
+        writer.write_le("count", 0x1234_u16).unwrap();
+        writer.write_prefixed::<u32>("data", b"abc").unwrap();
+        writer.pad_to_alignment("padding", 4).unwrap();
+        let bytes = writer.into_inner();
+        assert_eq!(bytes, b"\x34\x12\x03\x00\x00\x00abc\x00\x00\x00");
+
+        let mut reader = Cursor::new(bytes);
+        assert_eq!(reader.read_le::<u16>("count").unwrap(), 0x1234);
+        assert_eq!(
+            reader.read_prefixed::<u32>("data").unwrap(),
+            (3, b"abc".to_vec())
+        );
+        reader.skip_to_alignment("padding", 4).unwrap();
+        assert_eq!(reader.position(), 12);
+    }
+
+    #[test]
+    fn corrupt_length_prefix_is_an_eof_error() {
+        let mut reader = Cursor::new(b"\xFF\xFF\xFF\xFFabc".to_vec());
+        let error = reader.read_prefixed::<u32>("data").unwrap_err();
+        assert!(error.is_eof());
+        assert_eq!(error.offset, 4);
+
+        let mut reader = Cursor::new(b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFFabc".to_vec());
+        let error = reader.read_prefixed::<u64>("data").unwrap_err();
+        assert!(error.is_eof());
+        assert_eq!(error.offset, 8);
+    }
+
+    #[test]
+    fn magic_mismatch_reports_both_headers() {
+        let mut reader = Cursor::new(b"[NAMTBL]".to_vec());
+        let error = reader.read_magic("magic", b"[STRTBL]").unwrap_err();
+        assert!(!error.is_eof());
+        assert_eq!(
+            error.source.to_string(),
+            "expected `[STRTBL]`, found `[NAMTBL]`"
+        );
+    }
+}
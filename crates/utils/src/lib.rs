@@ -1,3 +1,5 @@
+mod binary;
+
 mod utils {
     use anyhow::{Result as AnyResult, anyhow};
     use std::error::Error as StdError;
@@ -22,4 +24,5 @@ mod utils {
     }
 }
 
+pub use binary::*;
 pub use utils::*;